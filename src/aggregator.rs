@@ -0,0 +1,730 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{event, Level};
+
+use crate::{
+    api::receiver_api::RithmicResponse,
+    history::{Bar, HistoryQuery},
+    plants::history_plant::RithmicHistoryPlantHandle,
+    rti::{LastTrade, last_trade::TransactionType, messages::RithmicMessage},
+};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Completed bars kept by an aggregator unless `with_history` says otherwise
+const DEFAULT_HISTORY_LEN: usize = 10_000;
+
+const UPDATE_CHANNEL_CAPACITY: usize = 1024;
+
+/// Tolerance of the price comparisons of the range and renko bars
+const PRICE_EPSILON: f64 = 1e-9;
+
+/// Bars built locally from the trades
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AggregatedBarSpec {
+    /// Time bars of n seconds, aligned on the session start
+    Seconds(u32),
+    /// Number of trades per bar
+    Tick(u32),
+    /// Volume per bar, a trade overflowing a bar is split with the next one
+    Volume(u64),
+    /// High to low range of a bar, in ticks
+    Range { ticks: u32, tick_size: f64 },
+    /// Bricks of `ticks`, a reversal takes two bricks
+    Renko { ticks: u32, tick_size: f64 },
+}
+
+/// Volume traded at one price of a bar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FootprintLevel {
+    pub price: f64,
+    pub volume: u64,
+    /// Volume of the sell aggressors
+    pub bid_volume: u64,
+    /// Volume of the buy aggressors
+    pub ask_volume: u64,
+    pub num_trades: u64,
+}
+
+/// In-progress or completed bar, `footprint` is empty unless enabled with `with_footprint`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarUpdate {
+    pub bar: Bar,
+    pub footprint: Vec<FootprintLevel>,
+    pub is_complete: bool,
+}
+
+#[derive(Debug)]
+struct BarState {
+    bar: Bar,
+    footprint: BTreeMap<i64, FootprintLevel>,
+    session: i64,
+    bucket: i64,
+}
+
+impl BarState {
+    fn add(&mut self, price: f64, size: u64, aggressor: Option<TransactionType>, tick_size: Option<f64>) {
+        if self.bar.num_trades == 0 && self.bar.volume == 0 {
+            self.bar.open = price;
+            self.bar.high = price;
+            self.bar.low = price;
+        }
+
+        self.bar.high = self.bar.high.max(price);
+        self.bar.low = self.bar.low.min(price);
+        self.bar.close = price;
+        self.bar.volume += size;
+        self.bar.num_trades += 1;
+
+        match aggressor {
+            Some(TransactionType::Buy) => self.bar.ask_volume += size,
+            Some(TransactionType::Sell) => self.bar.bid_volume += size,
+            None => {}
+        }
+
+        let Some(tick_size) = tick_size else {
+            return;
+        };
+
+        let ticks = (price / tick_size).round() as i64;
+
+        let level = self.footprint.entry(ticks).or_insert_with(|| FootprintLevel {
+            price: ticks as f64 * tick_size,
+            volume: 0,
+            bid_volume: 0,
+            ask_volume: 0,
+            num_trades: 0,
+        });
+
+        level.volume += size;
+        level.num_trades += 1;
+
+        match aggressor {
+            Some(TransactionType::Buy) => level.ask_volume += size,
+            Some(TransactionType::Sell) => level.bid_volume += size,
+            None => {}
+        }
+    }
+
+    fn update(&self, is_complete: bool) -> BarUpdate {
+        BarUpdate {
+            bar: self.bar.clone(),
+            footprint: self.footprint.values().cloned().collect(),
+            is_complete,
+        }
+    }
+}
+
+/// Builds bars Rithmic does not provide from the `LastTrade` updates of the ticker plant.
+///
+/// Every trade returns the bars it completed followed by the in-progress bar. Bars only complete
+/// on a trade, a seconds bar stays open until the first trade of the next one.
+///
+/// ```ignore
+/// let mut aggregator = BarAggregator::new("ESM5", "CME", AggregatedBarSpec::Seconds(15))
+///     .with_footprint(0.25);
+///
+/// aggregator.seed_from_history(&history_plant, &query).await?;
+///
+/// let mut updates = aggregator.track(ticker_plant.resubscribe());
+/// ```
+#[derive(Debug)]
+pub struct BarAggregator {
+    symbol: String,
+    exchange: String,
+    spec: AggregatedBarSpec,
+    /// Seconds after midnight UTC, bars never span two sessions when set
+    session_start: Option<i64>,
+    footprint_tick_size: Option<f64>,
+    max_history: usize,
+    current: Option<BarState>,
+    history: VecDeque<Bar>,
+    /// Open and close of the last renko brick
+    renko: Option<(f64, f64)>,
+    /// Trades before this time are already part of the seeded bars
+    seeded_until: Option<DateTime<Utc>>,
+}
+
+impl BarAggregator {
+    pub fn new(symbol: &str, exchange: &str, spec: AggregatedBarSpec) -> BarAggregator {
+        BarAggregator {
+            symbol: symbol.to_string(),
+            exchange: exchange.to_string(),
+            spec,
+            session_start: None,
+            footprint_tick_size: None,
+            max_history: DEFAULT_HISTORY_LEN,
+            current: None,
+            history: VecDeque::new(),
+            renko: None,
+            seeded_until: None,
+        }
+    }
+
+    /// Closes the bars at `seconds_of_day` (UTC) and aligns the seconds bars on it
+    pub fn with_session_start(mut self, seconds_of_day: u32) -> Self {
+        self.session_start = Some(i64::from(seconds_of_day) % SECONDS_PER_DAY);
+        self
+    }
+
+    /// Keeps the volume traded at each price, rounded to `tick_size`
+    pub fn with_footprint(mut self, tick_size: f64) -> Self {
+        self.footprint_tick_size = Some(tick_size);
+        self
+    }
+
+    /// Number of completed bars kept in `history`
+    pub fn with_history(mut self, max_bars: usize) -> Self {
+        self.max_history = max_bars;
+        self
+    }
+
+    /// Completed bars, oldest first
+    pub fn history(&self) -> &VecDeque<Bar> {
+        &self.history
+    }
+
+    pub fn current(&self) -> Option<BarUpdate> {
+        self.current.as_ref().map(|state| state.update(false))
+    }
+
+    /// Adds already completed bars, oldest first, so the history is warm before the first trade.
+    ///
+    /// Trades older than the end of the last seeded bar are ignored, the trade based bars only
+    /// know when their last bar started. Renko bricks are rebuilt from the closes of the seeded
+    /// bars, each brick carries the volume of the bars since the previous one.
+    pub fn seed(&mut self, bars: impl IntoIterator<Item = Bar>) {
+        // Volume, bid volume, ask volume and trades of the seeded bars not in a brick yet
+        let mut unbricked = (0, 0, 0, 0);
+
+        for bar in bars {
+            let until = match self.spec {
+                AggregatedBarSpec::Seconds(seconds) => {
+                    bar.timestamp + chrono::Duration::seconds(seconds.into())
+                }
+                _ => bar.timestamp,
+            };
+
+            self.seeded_until = Some(self.seeded_until.map_or(until, |u| u.max(until)));
+
+            let AggregatedBarSpec::Renko { ticks, tick_size } = self.spec else {
+                self.push_history(bar);
+
+                continue;
+            };
+
+            unbricked.0 += bar.volume;
+            unbricked.1 += bar.bid_volume;
+            unbricked.2 += bar.ask_volume;
+            unbricked.3 += bar.num_trades;
+
+            let Some((mut open, mut close)) = self.renko else {
+                self.renko = Some((bar.close, bar.close));
+
+                continue;
+            };
+
+            let brick = f64::from(ticks) * tick_size;
+
+            while let Some((from, to)) = next_brick(open, close, bar.close, brick) {
+                let (volume, bid_volume, ask_volume, num_trades) = std::mem::take(&mut unbricked);
+
+                self.push_history(Bar {
+                    symbol: self.symbol.clone(),
+                    exchange: self.exchange.clone(),
+                    timestamp: bar.timestamp,
+                    open: from,
+                    high: from.max(to),
+                    low: from.min(to),
+                    close: to,
+                    volume,
+                    bid_volume,
+                    ask_volume,
+                    num_trades,
+                });
+
+                (open, close) = (from, to);
+                self.renko = Some((open, close));
+            }
+        }
+    }
+
+    /// Seeds the aggregator with the bars of a history replay
+    pub async fn seed_from_history(
+        &mut self,
+        history_plant: &RithmicHistoryPlantHandle,
+        query: &HistoryQuery,
+    ) -> Result<usize, String> {
+        let mut bars = history_plant.history(query).await?;
+
+        bars.sort_by_key(|bar| bar.timestamp);
+
+        let len = bars.len();
+
+        self.seed(bars);
+
+        Ok(len)
+    }
+
+    /// Feeds a plant update, anything but a trade of the symbol is ignored
+    pub fn apply(&mut self, response: &RithmicResponse) -> Vec<BarUpdate> {
+        match &response.message {
+            RithmicMessage::LastTrade(trade)
+                if trade.symbol.as_deref() == Some(self.symbol.as_str())
+                    && trade.exchange.as_deref() == Some(self.exchange.as_str()) =>
+            {
+                self.on_trade(trade)
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn on_trade(&mut self, trade: &LastTrade) -> Vec<BarUpdate> {
+        // The snapshot sent on subscription repeats a trade that is already over
+        if trade.is_snapshot == Some(true) {
+            return vec![];
+        }
+
+        let (Some(price), Some(size)) = (trade.trade_price, trade.trade_size) else {
+            return vec![];
+        };
+
+        let Ok(size) = u64::try_from(size) else {
+            return vec![];
+        };
+
+        let time = trade
+            .ssboe
+            .and_then(|ssboe| {
+                let nanos = u32::try_from(trade.usecs.unwrap_or_default()).unwrap_or_default() * 1000;
+
+                DateTime::from_timestamp(ssboe.into(), nanos)
+            })
+            .unwrap_or_else(Utc::now);
+
+        if self.seeded_until.is_some_and(|until| time < until) {
+            return vec![];
+        }
+
+        let aggressor = trade
+            .aggressor
+            .and_then(|aggressor| TransactionType::try_from(aggressor).ok());
+
+        let mut updates = vec![];
+
+        self.add_trade(time, price, size, aggressor, &mut updates);
+
+        if let Some(current) = &self.current {
+            updates.push(current.update(false));
+        }
+
+        updates
+    }
+
+    /// Aggregates a plant subscription on a separate task until the plant closes or the returned
+    /// receiver is dropped. In-progress updates are dropped when the reader falls behind,
+    /// completed bars never are.
+    pub fn track(mut self, mut receiver: broadcast::Receiver<RithmicResponse>) -> mpsc::Receiver<BarUpdate> {
+        let (tx, rx) = mpsc::channel(UPDATE_CHANNEL_CAPACITY);
+
+        tokio::spawn(async move {
+            loop {
+                let response = match receiver.recv().await {
+                    Ok(response) => response,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        event!(Level::WARN, "bar_aggregator: lagged, {} updates skipped", skipped);
+
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                for update in self.apply(&response) {
+                    if update.is_complete {
+                        if tx.send(update).await.is_err() {
+                            return;
+                        }
+                    } else if let Err(mpsc::error::TrySendError::Closed(_)) = tx.try_send(update) {
+                        return;
+                    }
+                }
+            }
+        });
+
+        rx
+    }
+
+    fn add_trade(
+        &mut self,
+        time: DateTime<Utc>,
+        price: f64,
+        size: u64,
+        aggressor: Option<TransactionType>,
+        updates: &mut Vec<BarUpdate>,
+    ) {
+        let session = self.session_of(time);
+
+        let bucket = match self.spec {
+            AggregatedBarSpec::Seconds(seconds) => self.bucket_of(time, seconds),
+            _ => 0,
+        };
+
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|current| current.session != session || current.bucket != bucket)
+        {
+            self.complete(updates);
+
+            // A renko brick never spans two sessions either
+            if let Some((_, close)) = self.renko {
+                self.renko = Some((close, close));
+            }
+        }
+
+        let tick_size = self.footprint_tick_size;
+
+        match self.spec {
+            AggregatedBarSpec::Seconds(_) => {
+                self.open(time, price, session, bucket).add(price, size, aggressor, tick_size);
+            }
+            AggregatedBarSpec::Tick(trades) => {
+                let state = self.open(time, price, session, bucket);
+
+                state.add(price, size, aggressor, tick_size);
+
+                if state.bar.num_trades >= u64::from(trades) {
+                    self.complete(updates);
+                }
+            }
+            AggregatedBarSpec::Volume(volume) => {
+                let volume = volume.max(1);
+                let mut remaining = size;
+
+                while remaining > 0 {
+                    let state = self.open(time, price, session, bucket);
+                    let part = remaining.min(volume.saturating_sub(state.bar.volume).max(1));
+
+                    state.add(price, part, aggressor, tick_size);
+                    remaining -= part;
+
+                    if state.bar.volume >= volume {
+                        self.complete(updates);
+                    }
+                }
+            }
+            AggregatedBarSpec::Range { ticks, tick_size: range_tick_size } => {
+                let range = f64::from(ticks) * range_tick_size;
+
+                if self.current.as_ref().is_some_and(|current| {
+                    current.bar.high.max(price) - current.bar.low.min(price) > range + PRICE_EPSILON
+                }) {
+                    self.complete(updates);
+                }
+
+                self.open(time, price, session, bucket).add(price, size, aggressor, tick_size);
+            }
+            AggregatedBarSpec::Renko { ticks, tick_size: brick_tick_size } => {
+                let brick = f64::from(ticks) * brick_tick_size;
+
+                self.add_renko(time, price, size, aggressor, brick, session, updates);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_renko(
+        &mut self,
+        time: DateTime<Utc>,
+        price: f64,
+        size: u64,
+        aggressor: Option<TransactionType>,
+        brick: f64,
+        session: i64,
+        updates: &mut Vec<BarUpdate>,
+    ) {
+        let tick_size = self.footprint_tick_size;
+
+        self.open(time, price, session, 0).add(price, size, aggressor, tick_size);
+
+        let Some((mut open, mut close)) = self.renko else {
+            self.renko = Some((price, price));
+
+            return;
+        };
+
+        while let Some((from, to)) = next_brick(open, close, price, brick) {
+            let state = self.open(time, from, session, 0);
+
+            state.bar.open = from;
+            state.bar.close = to;
+            state.bar.high = from.max(to);
+            state.bar.low = from.min(to);
+
+            self.complete(updates);
+
+            (open, close) = (from, to);
+            self.renko = Some((open, close));
+        }
+    }
+
+    /// Current bar, opened at `price` when there is none
+    fn open(&mut self, time: DateTime<Utc>, price: f64, session: i64, bucket: i64) -> &mut BarState {
+        let timestamp = match self.spec {
+            AggregatedBarSpec::Seconds(seconds) => {
+                let anchor = self.session_start.unwrap_or_default();
+
+                DateTime::from_timestamp(anchor + bucket * i64::from(seconds.max(1)), 0)
+                    .unwrap_or(time)
+            }
+            _ => time,
+        };
+
+        self.current.get_or_insert_with(|| BarState {
+            bar: Bar {
+                symbol: self.symbol.clone(),
+                exchange: self.exchange.clone(),
+                timestamp,
+                open: price,
+                high: price,
+                low: price,
+                close: price,
+                volume: 0,
+                bid_volume: 0,
+                ask_volume: 0,
+                num_trades: 0,
+            },
+            footprint: BTreeMap::new(),
+            session,
+            bucket,
+        })
+    }
+
+    fn complete(&mut self, updates: &mut Vec<BarUpdate>) {
+        let Some(state) = self.current.take() else {
+            return;
+        };
+
+        updates.push(state.update(true));
+
+        self.push_history(state.bar);
+    }
+
+    fn push_history(&mut self, bar: Bar) {
+        self.history.push_back(bar);
+
+        while self.history.len() > self.max_history {
+            self.history.pop_front();
+        }
+    }
+
+    fn session_of(&self, time: DateTime<Utc>) -> i64 {
+        match self.session_start {
+            Some(start) => (time.timestamp() - start).div_euclid(SECONDS_PER_DAY),
+            None => 0,
+        }
+    }
+
+    fn bucket_of(&self, time: DateTime<Utc>, seconds: u32) -> i64 {
+        let anchor = self.session_start.unwrap_or_default();
+
+        (time.timestamp() - anchor).div_euclid(i64::from(seconds.max(1)))
+    }
+}
+
+/// Open and close of the brick `price` completes after the brick from `open` to `close`, None
+/// when it completes none
+fn next_brick(open: f64, close: f64, price: f64, brick: f64) -> Option<(f64, f64)> {
+    if brick <= 0.0 {
+        return None;
+    }
+
+    // A reversal starts from the open of the last brick
+    let up_from = if close >= open { close } else { open };
+    let down_from = if close <= open { close } else { open };
+
+    if price >= up_from + brick - PRICE_EPSILON {
+        Some((up_from, up_from + brick))
+    } else if price <= down_from - brick + PRICE_EPSILON {
+        Some((down_from, down_from - brick))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn trade(time: DateTime<Utc>, price: f64, size: i32, aggressor: Option<TransactionType>) -> LastTrade {
+        LastTrade {
+            symbol: Some("ESM5".to_string()),
+            exchange: Some("CME".to_string()),
+            trade_price: Some(price),
+            trade_size: Some(size),
+            aggressor: aggressor.map(i32::from),
+            ssboe: Some(time.timestamp() as i32),
+            usecs: Some(0),
+            ..LastTrade::default()
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 2, hour, minute, 0).unwrap()
+    }
+
+    fn completed(updates: &[BarUpdate]) -> Vec<&Bar> {
+        updates.iter().filter(|update| update.is_complete).map(|update| &update.bar).collect()
+    }
+
+    #[test]
+    fn renko_reverses_after_two_bricks() {
+        let spec = AggregatedBarSpec::Renko { ticks: 4, tick_size: 0.25 };
+        let mut aggregator = BarAggregator::new("ESM5", "CME", spec);
+
+        assert!(completed(&aggregator.on_trade(&trade(at(14, 0), 100.0, 1, None))).is_empty());
+
+        let updates = aggregator.on_trade(&trade(at(14, 1), 102.0, 1, None));
+        let bricks = completed(&updates);
+
+        assert_eq!(
+            bricks.iter().map(|bar| (bar.open, bar.close)).collect::<Vec<_>>(),
+            vec![(100.0, 101.0), (101.0, 102.0)]
+        );
+
+        // One brick below the close is not a reversal yet, it starts from the open of the last brick
+        assert!(completed(&aggregator.on_trade(&trade(at(14, 2), 101.0, 1, None))).is_empty());
+        assert!(completed(&aggregator.on_trade(&trade(at(14, 3), 100.5, 1, None))).is_empty());
+
+        let updates = aggregator.on_trade(&trade(at(14, 4), 100.0, 1, None));
+        let bricks = completed(&updates);
+
+        assert_eq!(bricks.len(), 1);
+        assert_eq!((bricks[0].open, bricks[0].close), (101.0, 100.0));
+    }
+
+    #[test]
+    fn renko_is_seeded_with_bricks_rebuilt_from_the_closes() {
+        let spec = AggregatedBarSpec::Renko { ticks: 4, tick_size: 0.25 };
+        let mut aggregator = BarAggregator::new("ESM5", "CME", spec);
+
+        let closes = [100.0, 100.5, 101.2, 102.1, 99.9];
+
+        let bars = closes.into_iter().enumerate().map(|(minute, close)| Bar {
+            symbol: "ESM5".to_string(),
+            exchange: "CME".to_string(),
+            timestamp: at(13, minute as u32),
+            open: close - 5.0,
+            high: close + 5.0,
+            low: close - 5.0,
+            close,
+            volume: 1,
+            bid_volume: 0,
+            ask_volume: 1,
+            num_trades: 1,
+        });
+
+        aggregator.seed(bars);
+
+        assert_eq!(
+            aggregator
+                .history()
+                .iter()
+                .map(|bar| (bar.open, bar.close, bar.volume))
+                .collect::<Vec<_>>(),
+            vec![(100.0, 101.0, 3), (101.0, 102.0, 1), (101.0, 100.0, 1)]
+        );
+
+        assert!(completed(&aggregator.on_trade(&trade(at(14, 0), 100.5, 1, None))).is_empty());
+
+        let updates = aggregator.on_trade(&trade(at(14, 1), 99.0, 1, None));
+        let bricks = completed(&updates);
+
+        assert_eq!(bricks.len(), 1);
+        assert_eq!((bricks[0].open, bricks[0].close), (100.0, 99.0));
+    }
+
+    #[test]
+    fn volume_bars_split_the_overflowing_trade() {
+        let mut aggregator = BarAggregator::new("ESM5", "CME", AggregatedBarSpec::Volume(10));
+
+        assert!(completed(&aggregator.on_trade(&trade(at(14, 0), 100.0, 7, None))).is_empty());
+
+        let updates = aggregator.on_trade(&trade(at(14, 1), 100.25, 8, None));
+
+        assert_eq!(completed(&updates).iter().map(|bar| bar.volume).collect::<Vec<_>>(), vec![10]);
+        assert_eq!(aggregator.current().map(|update| update.bar.volume), Some(5));
+
+        let updates = aggregator.on_trade(&trade(at(14, 2), 100.5, 15, None));
+
+        assert_eq!(completed(&updates).iter().map(|bar| bar.volume).collect::<Vec<_>>(), vec![10, 10]);
+        assert!(aggregator.current().is_none());
+        assert_eq!(aggregator.history().iter().map(|bar| bar.volume).sum::<u64>(), 30);
+    }
+
+    #[test]
+    fn range_bars_close_when_the_range_is_exceeded() {
+        let spec = AggregatedBarSpec::Range { ticks: 4, tick_size: 0.25 };
+        let mut aggregator = BarAggregator::new("ESM5", "CME", spec);
+
+        for (minute, price) in [(0, 100.0), (1, 100.75), (2, 101.0)] {
+            assert!(completed(&aggregator.on_trade(&trade(at(14, minute), price, 1, None))).is_empty());
+        }
+
+        let updates = aggregator.on_trade(&trade(at(14, 3), 101.25, 2, None));
+        let bars = completed(&updates);
+
+        assert_eq!(bars.len(), 1);
+        assert_eq!((bars[0].low, bars[0].high, bars[0].volume), (100.0, 101.0, 3));
+
+        let current = aggregator.current().unwrap().bar;
+
+        assert_eq!((current.open, current.volume), (101.25, 2));
+    }
+
+    #[test]
+    fn seconds_buckets_are_anchored_on_the_session_start() {
+        let mut aggregator = BarAggregator::new("ESM5", "CME", AggregatedBarSpec::Seconds(3600))
+            .with_session_start(22 * 3600 + 30 * 60);
+
+        aggregator.on_trade(&trade(at(22, 20), 100.0, 1, None));
+
+        assert_eq!(aggregator.current().unwrap().bar.timestamp, at(21, 30));
+
+        let updates = aggregator.on_trade(&trade(at(22, 40), 100.25, 1, None));
+
+        assert_eq!(completed(&updates).iter().map(|bar| bar.timestamp).collect::<Vec<_>>(), vec![at(21, 30)]);
+        assert_eq!(aggregator.current().unwrap().bar.timestamp, at(22, 30));
+
+        aggregator.on_trade(&trade(at(23, 29), 100.5, 1, None));
+
+        let updates = aggregator.on_trade(&trade(at(23, 30), 100.75, 1, None));
+
+        assert_eq!(completed(&updates).iter().map(|bar| bar.timestamp).collect::<Vec<_>>(), vec![at(22, 30)]);
+        assert_eq!(aggregator.current().unwrap().bar.timestamp, at(23, 30));
+    }
+
+    #[test]
+    fn footprint_rounds_trades_to_the_tick() {
+        let mut aggregator = BarAggregator::new("ESM5", "CME", AggregatedBarSpec::Tick(10)).with_footprint(0.25);
+
+        aggregator.on_trade(&trade(at(14, 0), 100.0, 2, Some(TransactionType::Buy)));
+        aggregator.on_trade(&trade(at(14, 0), 100.26, 3, Some(TransactionType::Sell)));
+        aggregator.on_trade(&trade(at(14, 0), 100.24, 1, Some(TransactionType::Buy)));
+
+        let footprint = aggregator.current().unwrap().footprint;
+
+        assert_eq!(
+            footprint,
+            vec![
+                FootprintLevel { price: 100.0, volume: 2, bid_volume: 0, ask_volume: 2, num_trades: 1 },
+                FootprintLevel { price: 100.25, volume: 4, bid_volume: 3, ask_volume: 1, num_trades: 2 },
+            ]
+        );
+    }
+}
@@ -1,19 +1,57 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+use crate::{proxy::RithmicProxyConfig, throttle::RithmicThrottleConfig, tls::RithmicTlsConfig};
+
 pub mod receiver_api;
 pub mod rithmic_command_types;
+pub mod rithmic_instrument_types;
+pub mod rithmic_rms_types;
+pub mod rithmic_tick_size_types;
 pub mod sender_api;
 
 
 pub static DEFAULT_RTI_WS_URL: &str = "wss://rprotocol-mobile.rithmic.com";
+pub static DEFAULT_APP_NAME: &str = "pede:pts";
+pub static DEFAULT_APP_VERSION: &str = "1";
 
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RithmicConnectionInfo {
+    #[serde(default = "default_url")]
     pub url: String,
     pub user: String,
+    /// Left empty in config files, see `config::RithmicProfile`. Never serialized.
+    #[serde(default, skip_serializing)]
     pub password: String,
     pub system_name: String,
+    /// Application name registered with Rithmic, required for production conformance
+    #[serde(default = "default_app_name")]
+    pub app_name: String,
+    #[serde(default = "default_app_version")]
+    pub app_version: String,
+    #[serde(default)]
+    pub mac_addr: Vec<String>,
+    #[serde(default)]
+    pub os_version: Option<String>,
+    #[serde(default)]
+    pub os_platform: Option<String>,
+    /// Ask the ticker plant for aggregated quotes, ignored by the other plants
+    #[serde(default)]
+    pub aggregated_quotes: Option<bool>,
+    /// Proxy used by every plant, the proxy from `HTTPS_PROXY`/`ALL_PROXY` is used when None
+    #[serde(default)]
+    pub proxy: Option<RithmicProxyConfig>,
+    /// TLS settings used by every plant, the system root certificates are trusted when None
+    #[serde(default)]
+    pub tls: Option<RithmicTlsConfig>,
+    /// Rate limit of the order plant requests, unlimited when None
+    #[serde(default)]
+    pub order_throttle: Option<RithmicThrottleConfig>,
+    /// Rate limit of the ticker plant requests, unlimited when None
+    #[serde(default)]
+    pub ticker_throttle: Option<RithmicThrottleConfig>,
 }
 
 impl Default for RithmicConnectionInfo {
@@ -23,7 +61,51 @@ impl Default for RithmicConnectionInfo {
             user: "".to_string(),
             password: "".to_string(),
             system_name: "".to_string(),
+            app_name: DEFAULT_APP_NAME.to_string(),
+            app_version: DEFAULT_APP_VERSION.to_string(),
+            mac_addr: vec![],
+            os_version: None,
+            os_platform: None,
+            aggregated_quotes: None,
+            proxy: None,
+            tls: None,
+            order_throttle: None,
+            ticker_throttle: None,
         }
 
     }
-}
\ No newline at end of file
+}
+
+/// Same as a derived `Debug` with the password redacted
+impl fmt::Debug for RithmicConnectionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RithmicConnectionInfo")
+            .field("url", &self.url)
+            .field("user", &self.user)
+            .field("password", &"<redacted>")
+            .field("system_name", &self.system_name)
+            .field("app_name", &self.app_name)
+            .field("app_version", &self.app_version)
+            .field("mac_addr", &self.mac_addr)
+            .field("os_version", &self.os_version)
+            .field("os_platform", &self.os_platform)
+            .field("aggregated_quotes", &self.aggregated_quotes)
+            .field("proxy", &self.proxy)
+            .field("tls", &self.tls)
+            .field("order_throttle", &self.order_throttle)
+            .field("ticker_throttle", &self.ticker_throttle)
+            .finish()
+    }
+}
+
+fn default_url() -> String {
+    DEFAULT_RTI_WS_URL.to_string()
+}
+
+fn default_app_name() -> String {
+    DEFAULT_APP_NAME.to_string()
+}
+
+fn default_app_version() -> String {
+    DEFAULT_APP_VERSION.to_string()
+}
@@ -114,7 +114,7 @@ impl RithmicReceiverApi {
                 let error = self.get_error(&resp.rp_code);
 
                 RithmicResponse {
-                    request_id: resp.user_msg[0].clone(),
+                    request_id: resp.user_msg.first().cloned().unwrap_or_default(),
                     message: RithmicMessage::Reject(resp),
                     is_update: false,
                     has_more: false,
@@ -165,6 +165,53 @@ impl RithmicReceiverApi {
                     source: self.source.clone(),
                 }
             }
+            104 => {
+                let resp =
+                    ResponseGetInstrumentByUnderlyingKeys::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let error = self.get_error(&resp.rp_code);
+
+                // Expirations found for the underlying, sent next to the instruments and not
+                // part of their response
+                RithmicResponse {
+                    request_id: resp.user_msg.first().cloned().unwrap_or_default(),
+                    message: RithmicMessage::ResponseGetInstrumentByUnderlyingKeys(resp),
+                    is_update: true,
+                    has_more: false,
+                    multi_response: false,
+                    error,
+                    source: self.source.clone(),
+                }
+            }
+            106 => {
+                let resp =
+                    ResponseMarketDataUpdateByUnderlying::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let error = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseMarketDataUpdateByUnderlying(resp),
+                    is_update: false,
+                    has_more: false,
+                    multi_response: false,
+                    error,
+                    source: self.source.clone(),
+                }
+            }
+            108 => {
+                let resp = ResponseGiveTickSizeTypeTable::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let has_more = self.has_multiple(&resp.rq_handler_rp_code);
+                let error = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseGiveTickSizeTypeTable(resp),
+                    is_update: false,
+                    has_more,
+                    multi_response: true,
+                    error,
+                    source: self.source.clone(),
+                }
+            }
             110 => {
                 let resp = ResponseSearchSymbols::decode(&mut Cursor::new(&data[4..])).unwrap();
                 let has_more = self.has_multiple(&resp.rq_handler_rp_code);
@@ -195,6 +242,35 @@ impl RithmicReceiverApi {
                     source: self.source.clone(),
                 }
             }
+            120 => {
+                let resp = ResponseGetVolumeAtPrice::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let has_more = self.has_multiple(&resp.rq_handler_rp_code);
+                let error = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseGetVolumeAtPrice(resp),
+                    is_update: false,
+                    has_more,
+                    multi_response: true,
+                    error,
+                    source: self.source.clone(),
+                }
+            }
+            122 => {
+                let resp = ResponseAuxilliaryReferenceData::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let error = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseAuxilliaryReferenceData(resp),
+                    is_update: false,
+                    has_more: false,
+                    multi_response: false,
+                    error,
+                    source: self.source.clone(),
+                }
+            }
             150 => {
                 let resp = LastTrade::decode(&mut Cursor::new(&data[4..])).unwrap();
 
@@ -333,6 +409,22 @@ impl RithmicReceiverApi {
                     source: self.source.clone(),
                 }
             }
+            211 => {
+                let resp = ResponseResumeBars::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let error = self.get_error(&resp.rp_code);
+                // The resumed bars follow the acknowledgement under the same request id
+                let has_more = error.is_none();
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseResumeBars(resp),
+                    is_update: false,
+                    has_more,
+                    multi_response: true,
+                    error,
+                    source: self.source.clone(),
+                }
+            }
             250 => {
                 let resp = TimeBar::decode(&mut Cursor::new(&data[4..])).unwrap();
 
@@ -696,6 +788,19 @@ impl RithmicReceiverApi {
                     source: self.source.clone(),
                 }
             }
+            356 => {
+                let resp = AccountRmsUpdates::decode(&mut Cursor::new(&data[4..])).unwrap();
+
+                RithmicResponse {
+                    request_id: "".to_string(),
+                    message: RithmicMessage::AccountRmsUpdates(resp),
+                    is_update: true,
+                    has_more: false,
+                    multi_response: false,
+                    error: None,
+                    source: self.source.clone(),
+                }
+            }
             3505 => {
                 let resp = ResponseExitPosition::decode(&mut Cursor::new(&data[4..])).unwrap();
                 let has_more = self.has_multiple(&resp.rq_handler_rp_code);
@@ -711,6 +816,34 @@ impl RithmicReceiverApi {
                     source: self.source.clone(),
                 }
             }
+            3507 => {
+                let resp = ResponseReplayExecutions::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let err = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseReplayExecutions(resp),
+                    is_update: false,
+                    has_more: false,
+                    multi_response: false,
+                    error: err,
+                    source: self.source.clone(),
+                }
+            }
+            3509 => {
+                let resp = ResponseAccountRmsUpdates::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let err = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseAccountRmsUpdates(resp),
+                    is_update: false,
+                    has_more: false,
+                    multi_response: false,
+                    error: err,
+                    source: self.source.clone(),
+                }
+            }
             401 => {
                 let resp =
                     ResponsePnLPositionUpdates::decode(&mut Cursor::new(&data[4..])).unwrap();
@@ -768,23 +901,95 @@ impl RithmicReceiverApi {
                     source: self.source.clone(),
                 }
             }
+            501 => {
+                let resp =
+                    ResponseListUnacceptedAgreements::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let has_more = self.has_multiple(&resp.rq_handler_rp_code);
+                let err = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseListUnacceptedAgreements(resp),
+                    is_update: false,
+                    has_more,
+                    multi_response: true,
+                    error: err,
+                    source: self.source.clone(),
+                }
+            }
+            503 => {
+                let resp =
+                    ResponseListAcceptedAgreements::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let has_more = self.has_multiple(&resp.rq_handler_rp_code);
+                let err = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseListAcceptedAgreements(resp),
+                    is_update: false,
+                    has_more,
+                    multi_response: true,
+                    error: err,
+                    source: self.source.clone(),
+                }
+            }
+            505 => {
+                let resp = ResponseAcceptAgreement::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let err = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseAcceptAgreement(resp),
+                    is_update: false,
+                    has_more: false,
+                    multi_response: false,
+                    error: err,
+                    source: self.source.clone(),
+                }
+            }
+            507 => {
+                let resp = ResponseShowAgreement::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let has_more = self.has_multiple(&resp.rq_handler_rp_code);
+                let err = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseShowAgreement(resp),
+                    is_update: false,
+                    has_more,
+                    multi_response: true,
+                    error: err,
+                    source: self.source.clone(),
+                }
+            }
+            509 => {
+                let resp =
+                    ResponseSetRithmicMrktDataSelfCertStatus::decode(&mut Cursor::new(&data[4..])).unwrap();
+                let err = self.get_error(&resp.rp_code);
+
+                RithmicResponse {
+                    request_id: resp.user_msg[0].clone(),
+                    message: RithmicMessage::ResponseSetRithmicMrktDataSelfCertStatus(resp),
+                    is_update: false,
+                    has_more: false,
+                    multi_response: false,
+                    error: err,
+                    source: self.source.clone(),
+                }
+            }
             _ => {
                 panic!("Unknown message type: {:#01x?}", parsed_message)
             }
         };
 
-        // Handle errors
-        let err = self.check_message_error(&response);
-
-        if let Some(error) = err {
+        // Errors are passed on with their response, the plant fails the request they answer
+        if let Some(error) = self.check_message_error(&response) {
             event!(
                 Level::ERROR,
                 "receiver_api: error {:#?} {:?}",
                 response,
                 error
             );
-
-            return Err(error);
         }
 
         Ok(response)
@@ -800,7 +1005,7 @@ impl RithmicReceiverApi {
         } else {
             event!(Level::ERROR, "receiver_api: error {:#?}", rp_code);
 
-            Some(rp_code[1].clone())
+            rp_code.get(1).or(rp_code.first()).cloned()
         }
     }
 
@@ -1,3 +1,28 @@
+use crate::rti::{
+    request_bracket_order::{BracketType, Duration, PriceType, TransactionType},
+    request_new_order,
+};
+
+/// Account context shared by the plants that send account scoped requests
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RithmicAccount {
+    pub account_id: String,
+    pub fcm_id: String,
+    pub ib_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RithmicOrder {
+    pub action: request_new_order::TransactionType,
+    pub duration: Option<request_new_order::Duration>,
+    pub exchange: String,
+    pub localid: String,
+    pub ordertype: request_new_order::PriceType,
+    pub price: f64,
+    pub qty: i32,
+    pub symbol: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct RithmicBracketOrder {
     pub action: i32,
@@ -10,6 +35,283 @@ pub struct RithmicBracketOrder {
     pub qty: i32,
     pub stop_ticks: i32,
     pub symbol: String,
+    // Defaults to TargetAndStopStatic when None
+    pub bracket_type: Option<i32>,
+    // Defaults to qty when None, ignored when the bracket type has no such leg
+    pub target_qty: Option<i32>,
+    pub stop_qty: Option<i32>,
+    pub trailing_stop: Option<RithmicTrailingStop>,
+    pub break_even: Option<RithmicBreakEven>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RithmicTrailingStop {
+    /// Number of ticks in profit before the stop starts trailing
+    pub trigger_ticks: i32,
+    /// Trail off the last trade price instead of the bid/offer
+    pub by_last_trade_price: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct RithmicBreakEven {
+    /// Offset of the stop from the fill price once break even is triggered
+    pub ticks: i32,
+    /// Number of ticks in profit before the stop is moved to break even
+    pub trigger_ticks: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RithmicBracketLeg {
+    pub qty: i32,
+    pub ticks: i32,
+}
+
+/// Builds one or more bracket orders from a set of target and stop legs.
+///
+/// Rithmic only accepts a single target and a single stop per bracket order, so when several
+/// legs are given the entry quantity is split into pieces at every leg boundary and each piece
+/// is sent as its own bracket order.
+///
+/// ```ignore
+/// // Buy 3, take 1 off at 8 ticks and 2 at 16 ticks, all protected by a 10 tick stop
+/// let orders = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 3)
+///     .target(1, 8)
+///     .target(2, 16)
+///     .stop(3, 10)
+///     .build()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct RithmicBracketOrderBuilder {
+    action: TransactionType,
+    bracket_type: Option<BracketType>,
+    break_even: Option<RithmicBreakEven>,
+    duration: Duration,
+    exchange: String,
+    localid: String,
+    ordertype: PriceType,
+    price: Option<f64>,
+    qty: i32,
+    stops: Vec<RithmicBracketLeg>,
+    symbol: String,
+    targets: Vec<RithmicBracketLeg>,
+    trailing_stop: Option<RithmicTrailingStop>,
+}
+
+impl RithmicBracketOrderBuilder {
+    pub fn new(symbol: &str, exchange: &str, action: TransactionType, qty: i32) -> Self {
+        RithmicBracketOrderBuilder {
+            action,
+            bracket_type: None,
+            break_even: None,
+            duration: Duration::Day,
+            exchange: exchange.to_string(),
+            localid: "".to_string(),
+            ordertype: PriceType::Market,
+            price: None,
+            qty,
+            stops: vec![],
+            symbol: symbol.to_string(),
+            targets: vec![],
+            trailing_stop: None,
+        }
+    }
+
+    pub fn localid(mut self, localid: &str) -> Self {
+        self.localid = localid.to_string();
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn limit(mut self, price: f64) -> Self {
+        self.ordertype = PriceType::Limit;
+        self.price = Some(price);
+        self
+    }
+
+    pub fn ordertype(mut self, ordertype: PriceType, price: Option<f64>) -> Self {
+        self.ordertype = ordertype;
+        self.price = price;
+        self
+    }
+
+    /// Overrides the bracket type derived from the legs
+    pub fn bracket_type(mut self, bracket_type: BracketType) -> Self {
+        self.bracket_type = Some(bracket_type);
+        self
+    }
+
+    pub fn target(mut self, qty: i32, ticks: i32) -> Self {
+        self.targets.push(RithmicBracketLeg { qty, ticks });
+        self
+    }
+
+    pub fn stop(mut self, qty: i32, ticks: i32) -> Self {
+        self.stops.push(RithmicBracketLeg { qty, ticks });
+        self
+    }
+
+    pub fn trailing_stop(mut self, trigger_ticks: i32, by_last_trade_price: bool) -> Self {
+        self.trailing_stop = Some(RithmicTrailingStop {
+            trigger_ticks,
+            by_last_trade_price,
+        });
+        self
+    }
+
+    pub fn break_even(mut self, ticks: i32, trigger_ticks: i32) -> Self {
+        self.break_even = Some(RithmicBreakEven {
+            ticks,
+            trigger_ticks,
+        });
+        self
+    }
+
+    pub fn build(self) -> Result<Vec<RithmicBracketOrder>, String> {
+        if self.qty <= 0 {
+            return Err("bracket order quantity must be positive".to_string());
+        }
+
+        if self.targets.is_empty() && self.stops.is_empty() {
+            return Err("bracket order needs at least one target or stop".to_string());
+        }
+
+        for leg in self.targets.iter().chain(self.stops.iter()) {
+            if leg.qty <= 0 || leg.ticks <= 0 {
+                return Err(format!("invalid bracket leg {:?}", leg));
+            }
+        }
+
+        let target_total: i32 = self.targets.iter().map(|l| l.qty).sum();
+        let stop_total: i32 = self.stops.iter().map(|l| l.qty).sum();
+
+        if target_total > self.qty || stop_total > self.qty {
+            return Err(format!(
+                "bracket legs exceed order quantity {} (targets {}, stops {})",
+                self.qty, target_total, stop_total
+            ));
+        }
+
+        // Both paths leave no part of the entry unprotected
+        let covered = target_total.max(stop_total);
+
+        if covered < self.qty {
+            return Err(format!(
+                "quantity {}..{} is not covered by any target or stop",
+                covered, self.qty
+            ));
+        }
+
+        if self.trailing_stop.is_some() && self.stops.is_empty() {
+            return Err("trailing stop requires a stop leg".to_string());
+        }
+
+        if self.break_even.is_some() && self.stops.is_empty() {
+            return Err("break even requires a stop leg".to_string());
+        }
+
+        if self.ordertype != PriceType::Market && self.price.is_none() {
+            return Err(format!("{:?} bracket order requires a price", self.ordertype));
+        }
+
+        // A single order can carry different target and stop quantities
+        if self.targets.len() <= 1 && self.stops.len() <= 1 {
+            let target = self.targets.first().cloned();
+            let stop = self.stops.first().cloned();
+
+            return Ok(vec![self.order_piece(self.qty, target, stop, &self.localid)]);
+        }
+
+        // Otherwise split the entry at every target and stop boundary
+        let mut boundaries = vec![self.qty];
+        boundaries.extend(Self::cumulative(&self.targets));
+        boundaries.extend(Self::cumulative(&self.stops));
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let mut orders = vec![];
+        let mut filled = 0;
+
+        for (i, boundary) in boundaries.into_iter().enumerate() {
+            let piece_qty = boundary - filled;
+            let target = Self::leg_at(&self.targets, filled, piece_qty);
+            let stop = Self::leg_at(&self.stops, filled, piece_qty);
+
+            let localid = if self.localid.is_empty() {
+                "".to_string()
+            } else {
+                format!("{}-{}", self.localid, i + 1)
+            };
+
+            orders.push(self.order_piece(piece_qty, target, stop, &localid));
+            filled = boundary;
+        }
+
+        Ok(orders)
+    }
+
+    fn cumulative(legs: &[RithmicBracketLeg]) -> Vec<i32> {
+        legs.iter()
+            .scan(0, |total, leg| {
+                *total += leg.qty;
+                Some(*total)
+            })
+            .collect()
+    }
+
+    /// Returns the leg covering the quantity range starting at `start`
+    fn leg_at(legs: &[RithmicBracketLeg], start: i32, qty: i32) -> Option<RithmicBracketLeg> {
+        let mut total = 0;
+
+        for leg in legs {
+            total += leg.qty;
+
+            if start < total {
+                return Some(RithmicBracketLeg {
+                    qty,
+                    ticks: leg.ticks,
+                });
+            }
+        }
+
+        None
+    }
+
+    fn order_piece(
+        &self,
+        qty: i32,
+        target: Option<RithmicBracketLeg>,
+        stop: Option<RithmicBracketLeg>,
+        localid: &str,
+    ) -> RithmicBracketOrder {
+        let bracket_type = self.bracket_type.unwrap_or(match (&target, &stop) {
+            (Some(_), None) => BracketType::TargetOnlyStatic,
+            (None, Some(_)) => BracketType::StopOnlyStatic,
+            _ => BracketType::TargetAndStopStatic,
+        });
+
+        RithmicBracketOrder {
+            action: self.action.into(),
+            duration: self.duration.into(),
+            exchange: self.exchange.clone(),
+            localid: localid.to_string(),
+            ordertype: self.ordertype.into(),
+            price: self.price,
+            profit_ticks: target.as_ref().map(|l| l.ticks).unwrap_or(0),
+            qty,
+            stop_ticks: stop.as_ref().map(|l| l.ticks).unwrap_or(0),
+            symbol: self.symbol.clone(),
+            bracket_type: Some(bracket_type.into()),
+            target_qty: target.map(|l| l.qty),
+            stop_qty: stop.as_ref().map(|l| l.qty),
+            trailing_stop: stop.as_ref().and(self.trailing_stop.clone()),
+            // Break even moves the stop, a piece without one has nothing to move
+            break_even: stop.and(self.break_even.clone()),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -26,3 +328,154 @@ pub struct RithmicModifyOrder {
 pub struct RithmicCancelOrder {
     pub id: String,
 }
+
+/// How the user consumes market data, declared when accepting an exchange agreement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RithmicMarketDataUsageCapacity {
+    Professional,
+    NonProfessional,
+}
+
+impl RithmicMarketDataUsageCapacity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RithmicMarketDataUsageCapacity::Professional => "Professional",
+            RithmicMarketDataUsageCapacity::NonProfessional => "Non-Professional",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legs(orders: &[RithmicBracketOrder]) -> Vec<(i32, i32, i32)> {
+        orders
+            .iter()
+            .map(|order| (order.qty, order.profit_ticks, order.stop_ticks))
+            .collect()
+    }
+
+    #[test]
+    fn single_legs_stay_one_order() {
+        let orders = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 3)
+            .localid("entry")
+            .target(2, 8)
+            .stop(3, 10)
+            .build()
+            .unwrap();
+
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].localid, "entry");
+        assert_eq!((orders[0].target_qty, orders[0].stop_qty), (Some(2), Some(3)));
+    }
+
+    #[test]
+    fn targets_split_the_entry() {
+        let orders = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 3)
+            .localid("entry")
+            .target(1, 8)
+            .target(2, 16)
+            .stop(3, 10)
+            .build()
+            .unwrap();
+
+        assert_eq!(legs(&orders), vec![(1, 8, 10), (2, 16, 10)]);
+        assert_eq!(
+            orders.iter().map(|order| order.localid.as_str()).collect::<Vec<_>>(),
+            vec!["entry-1", "entry-2"]
+        );
+        assert!(orders.iter().all(|order| order.target_qty == Some(order.qty)));
+    }
+
+    #[test]
+    fn entry_is_split_at_every_leg_boundary() {
+        let orders = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Sell, 4)
+            .target(2, 8)
+            .target(2, 16)
+            .stop(1, 4)
+            .stop(3, 10)
+            .build()
+            .unwrap();
+
+        assert_eq!(legs(&orders), vec![(1, 8, 4), (1, 8, 10), (2, 16, 10)]);
+        assert!(orders.iter().all(|order| order.localid.is_empty()));
+    }
+
+    #[test]
+    fn pieces_without_a_stop_are_target_only() {
+        let orders = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 3)
+            .target(1, 8)
+            .target(2, 16)
+            .stop(1, 10)
+            .trailing_stop(4, true)
+            .build()
+            .unwrap();
+
+        assert_eq!(legs(&orders), vec![(1, 8, 10), (2, 16, 0)]);
+        assert_eq!(
+            orders.iter().map(|order| order.bracket_type).collect::<Vec<_>>(),
+            vec![
+                Some(BracketType::TargetAndStopStatic.into()),
+                Some(BracketType::TargetOnlyStatic.into())
+            ]
+        );
+        assert!(orders[0].trailing_stop.is_some());
+        assert!(orders[1].trailing_stop.is_none());
+        assert_eq!(orders[1].stop_qty, None);
+    }
+
+    #[test]
+    fn single_legs_must_cover_the_entry() {
+        let uncovered = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 3)
+            .target(2, 8)
+            .build();
+
+        assert!(uncovered.is_err());
+
+        let covered = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 3)
+            .target(2, 8)
+            .stop(3, 10)
+            .build();
+
+        assert!(covered.is_ok());
+    }
+
+    #[test]
+    fn break_even_is_only_set_on_pieces_with_a_stop() {
+        let orders = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 3)
+            .target(1, 8)
+            .target(2, 16)
+            .stop(1, 10)
+            .break_even(1, 6)
+            .build()
+            .unwrap();
+
+        assert_eq!(legs(&orders), vec![(1, 8, 10), (2, 16, 0)]);
+        assert!(orders[0].break_even.is_some());
+        assert!(orders[1].break_even.is_none());
+
+        let without_stop = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 1)
+            .target(1, 8)
+            .break_even(1, 6)
+            .build();
+
+        assert!(without_stop.is_err());
+    }
+
+    #[test]
+    fn uncovered_or_oversized_legs_are_rejected() {
+        let uncovered = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 4)
+            .target(1, 8)
+            .target(1, 16)
+            .build();
+
+        assert!(uncovered.is_err());
+
+        let oversized = RithmicBracketOrderBuilder::new("ESZ5", "CME", TransactionType::Buy, 2)
+            .target(3, 8)
+            .build();
+
+        assert!(oversized.is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::rti::{
+    ResponseAuxilliaryReferenceData, ResponseGetInstrumentByUnderlying, ResponseReferenceData,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RithmicPutCall {
+    Put,
+    Call,
+}
+
+/// Reference data of an instrument, completed by its auxiliary reference data when Rithmic has
+/// some.
+///
+/// R | Protocol does not publish session hours, the trading dates bound when the instrument
+/// trades at all.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RithmicInstrument {
+    pub symbol: String,
+    pub exchange: String,
+    pub exchange_symbol: Option<String>,
+    pub name: Option<String>,
+    pub product_code: Option<String>,
+    pub instrument_type: Option<String>,
+    pub underlying_symbol: Option<String>,
+    pub currency: Option<String>,
+    pub expiration_date: Option<NaiveDate>,
+    pub is_tradable: bool,
+    pub tick_size: Option<f64>,
+    /// Set when the tick size depends on the price, see `RithmicTickSizeTable`
+    pub tick_size_type: Option<String>,
+    /// Value of a one point move for one contract
+    pub point_value: Option<f64>,
+    pub strike_price: Option<f64>,
+    pub put_call_indicator: Option<String>,
+    pub price_display_format: Option<String>,
+    pub settlement_method: Option<String>,
+    pub first_trading_date: Option<NaiveDate>,
+    pub last_trading_date: Option<NaiveDate>,
+    pub first_notice_date: Option<NaiveDate>,
+    pub last_notice_date: Option<NaiveDate>,
+    pub first_delivery_date: Option<NaiveDate>,
+    pub last_delivery_date: Option<NaiveDate>,
+    pub unit_of_measure: Option<String>,
+    pub unit_of_measure_qty: Option<f64>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl RithmicInstrument {
+    pub fn new(
+        reference_data: &ResponseReferenceData,
+        auxilliary: Option<&ResponseAuxilliaryReferenceData>,
+    ) -> Self {
+        let mut instrument = RithmicInstrument {
+            symbol: reference_data.symbol.clone().unwrap_or_default(),
+            exchange: reference_data.exchange.clone().unwrap_or_default(),
+            exchange_symbol: reference_data.exchange_symbol.clone(),
+            name: reference_data.symbol_name.clone(),
+            product_code: reference_data.product_code.clone(),
+            instrument_type: reference_data.instrument_type.clone(),
+            underlying_symbol: reference_data.underlying_symbol.clone(),
+            currency: reference_data.currency.clone(),
+            expiration_date: reference_data.expiration_date.as_deref().and_then(parse_date),
+            is_tradable: reference_data
+                .is_tradable
+                .as_deref()
+                .is_some_and(|tradable| tradable.eq_ignore_ascii_case("true")),
+            tick_size: reference_data.min_fprice_change,
+            tick_size_type: reference_data
+                .tick_size_type
+                .clone()
+                .filter(|tick_size_type| !tick_size_type.is_empty()),
+            point_value: reference_data.single_point_value,
+            strike_price: reference_data.strike_price,
+            put_call_indicator: reference_data.put_call_indicator.clone(),
+            price_display_format: reference_data.price_display_format.clone(),
+            fetched_at: Utc::now(),
+            ..RithmicInstrument::default()
+        };
+
+        if let Some(auxilliary) = auxilliary {
+            instrument.settlement_method = auxilliary.settlement_method.clone();
+            instrument.first_trading_date = auxilliary.first_trading_date.as_deref().and_then(parse_date);
+            instrument.last_trading_date = auxilliary.last_trading_date.as_deref().and_then(parse_date);
+            instrument.first_notice_date = auxilliary.first_notice_date.as_deref().and_then(parse_date);
+            instrument.last_notice_date = auxilliary.last_notice_date.as_deref().and_then(parse_date);
+            instrument.first_delivery_date =
+                auxilliary.first_delivery_date.as_deref().and_then(parse_date);
+            instrument.last_delivery_date =
+                auxilliary.last_delivery_date.as_deref().and_then(parse_date);
+            instrument.unit_of_measure = auxilliary.unit_of_measure.clone();
+            instrument.unit_of_measure_qty = auxilliary.unit_of_measure_qty;
+        }
+
+        instrument
+    }
+
+    /// Value of a one tick move for one contract
+    pub fn tick_value(&self) -> Option<f64> {
+        Some(self.tick_size? * self.point_value?)
+    }
+
+    /// None when the instrument is not an option
+    pub fn put_call(&self) -> Option<RithmicPutCall> {
+        let indicator = self.put_call_indicator.as_deref()?.trim();
+
+        if indicator.eq_ignore_ascii_case("put") || indicator.eq_ignore_ascii_case("p") {
+            Some(RithmicPutCall::Put)
+        } else if indicator.eq_ignore_ascii_case("call") || indicator.eq_ignore_ascii_case("c") {
+            Some(RithmicPutCall::Call)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `date` is between the first and last trading dates, true when they are unknown
+    pub fn is_trading_on(&self, date: NaiveDate) -> bool {
+        self.first_trading_date.is_none_or(|first| date >= first)
+            && self.last_trading_date.is_none_or(|last| date <= last)
+    }
+}
+
+/// Reference data of an instrument listed by underlying, `is_tradable` and the auxiliary
+/// reference data are not part of it
+impl From<&ResponseGetInstrumentByUnderlying> for RithmicInstrument {
+    fn from(row: &ResponseGetInstrumentByUnderlying) -> Self {
+        RithmicInstrument {
+            symbol: row.symbol.clone().unwrap_or_default(),
+            exchange: row.exchange.clone().unwrap_or_default(),
+            exchange_symbol: row.exchange_symbol.clone(),
+            name: row.symbol_name.clone(),
+            product_code: row.product_code.clone(),
+            instrument_type: row.instrument_type.clone(),
+            underlying_symbol: row.underlying_symbol.clone(),
+            currency: row.currency.clone(),
+            expiration_date: row.expiration_date.as_deref().and_then(parse_date),
+            tick_size: row.min_fprice_change,
+            tick_size_type: row
+                .tick_size_type
+                .clone()
+                .filter(|tick_size_type| !tick_size_type.is_empty()),
+            point_value: row.single_point_value,
+            strike_price: row.strike_price,
+            put_call_indicator: row.put_call_indicator.clone(),
+            price_display_format: row.price_display_format.clone(),
+            fetched_at: Utc::now(),
+            ..RithmicInstrument::default()
+        }
+    }
+}
+
+/// Instruments of an underlying, e.g. the options on a future
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RithmicOptionChain {
+    pub underlying_symbol: String,
+    pub exchange: String,
+    /// Ordered by expiration, strike, then calls before puts
+    pub instruments: Vec<RithmicInstrument>,
+}
+
+impl RithmicOptionChain {
+    pub fn new(
+        underlying_symbol: &str,
+        exchange: &str,
+        mut instruments: Vec<RithmicInstrument>,
+    ) -> Self {
+        instruments.sort_by(|a, b| {
+            let a_strike = a.strike_price.unwrap_or_default();
+            let b_strike = b.strike_price.unwrap_or_default();
+
+            a.expiration_date
+                .cmp(&b.expiration_date)
+                .then(a_strike.total_cmp(&b_strike))
+                .then(a.put_call_indicator.cmp(&b.put_call_indicator))
+                .then(a.symbol.cmp(&b.symbol))
+        });
+
+        RithmicOptionChain {
+            underlying_symbol: underlying_symbol.to_string(),
+            exchange: exchange.to_string(),
+            instruments,
+        }
+    }
+
+    pub fn expirations(&self) -> Vec<NaiveDate> {
+        let mut expirations: Vec<NaiveDate> = self
+            .instruments
+            .iter()
+            .filter_map(|instrument| instrument.expiration_date)
+            .collect();
+
+        expirations.dedup();
+
+        expirations
+    }
+
+    /// Strikes of an expiration, lowest first
+    pub fn strikes(&self, expiration: NaiveDate) -> Vec<f64> {
+        let mut strikes: Vec<f64> = self
+            .instruments
+            .iter()
+            .filter(|instrument| instrument.expiration_date == Some(expiration))
+            .filter_map(|instrument| instrument.strike_price)
+            .collect();
+
+        strikes.dedup();
+
+        strikes
+    }
+
+    pub fn option(
+        &self,
+        expiration: NaiveDate,
+        strike: f64,
+        put_call: RithmicPutCall,
+    ) -> Option<&RithmicInstrument> {
+        self.instruments.iter().find(|instrument| {
+            instrument.expiration_date == Some(expiration)
+                && instrument
+                    .strike_price
+                    .is_some_and(|strike_price| (strike_price - strike).abs() < 1e-9)
+                && instrument.put_call() == Some(put_call)
+        })
+    }
+
+    /// Whether an update of `symbol` is about an instrument of the chain
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.instruments.iter().any(|instrument| instrument.symbol == symbol)
+    }
+}
+
+/// Rithmic dates are `YYYYMMDD`
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date.trim(), "%Y%m%d").ok()
+}
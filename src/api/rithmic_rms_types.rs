@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use crate::rti::{
+    response_account_rms_info::{self, AutoLiquidateFlag},
+    response_product_rms_info, AccountRmsUpdates, ResponseAccountRmsInfo, ResponseProductRmsInfo,
+};
+
+/// Broker side limits of an account, built from the account and product RMS info
+#[derive(Debug, Clone, Default)]
+pub struct RithmicRiskLimits {
+    pub account_id: String,
+    pub currency: Option<String>,
+    pub status: Option<String>,
+    /// Maximum long position
+    pub buy_limit: Option<i32>,
+    /// Maximum short position
+    pub sell_limit: Option<i32>,
+    pub max_order_quantity: Option<i32>,
+    pub loss_limit: Option<f64>,
+    pub min_account_balance: Option<f64>,
+    pub auto_liquidate: bool,
+    pub auto_liquidate_threshold: Option<f64>,
+    /// Latest value pushed by `AccountRmsUpdates`
+    pub auto_liquidate_threshold_current: Option<f64>,
+    /// Per product code limits, which take precedence over the account limits
+    pub products: HashMap<String, RithmicProductLimits>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RithmicProductLimits {
+    pub product_code: String,
+    pub buy_limit: Option<i32>,
+    pub sell_limit: Option<i32>,
+    pub max_order_quantity: Option<i32>,
+    pub loss_limit: Option<f64>,
+}
+
+fn is_present(presence_bits: Option<u32>, bit: i32) -> bool {
+    presence_bits.is_some_and(|bits| bits & bit as u32 != 0)
+}
+
+impl From<&ResponseAccountRmsInfo> for RithmicRiskLimits {
+    fn from(info: &ResponseAccountRmsInfo) -> Self {
+        use response_account_rms_info::PresenceBits;
+
+        let bits = info.presence_bits;
+
+        RithmicRiskLimits {
+            account_id: info.account_id.clone().unwrap_or_default(),
+            currency: info.currency.clone(),
+            status: info.status.clone(),
+            buy_limit: info
+                .buy_limit
+                .filter(|_| is_present(bits, PresenceBits::BuyLimit as i32)),
+            sell_limit: info
+                .sell_limit
+                .filter(|_| is_present(bits, PresenceBits::SellLimit as i32)),
+            max_order_quantity: info
+                .max_order_quantity
+                .filter(|_| is_present(bits, PresenceBits::MaxOrderQuantity as i32)),
+            loss_limit: info
+                .loss_limit
+                .filter(|_| is_present(bits, PresenceBits::LossLimit as i32)),
+            min_account_balance: info
+                .min_account_balance
+                .filter(|_| is_present(bits, PresenceBits::MinAccountBalance as i32)),
+            auto_liquidate: info.auto_liquidate == Some(AutoLiquidateFlag::Enabled.into()),
+            auto_liquidate_threshold: info.auto_liquidate_threshold,
+            auto_liquidate_threshold_current: None,
+            products: HashMap::new(),
+        }
+    }
+}
+
+impl From<&ResponseProductRmsInfo> for RithmicProductLimits {
+    fn from(info: &ResponseProductRmsInfo) -> Self {
+        use response_product_rms_info::PresenceBits;
+
+        let bits = info.presence_bits;
+
+        RithmicProductLimits {
+            product_code: info.product_code.clone().unwrap_or_default(),
+            buy_limit: info
+                .buy_limit
+                .filter(|_| is_present(bits, PresenceBits::BuyLimit as i32)),
+            sell_limit: info
+                .sell_limit
+                .filter(|_| is_present(bits, PresenceBits::SellLimit as i32)),
+            max_order_quantity: info
+                .max_order_quantity
+                .filter(|_| is_present(bits, PresenceBits::MaxOrderQuantity as i32)),
+            loss_limit: info
+                .loss_limit
+                .filter(|_| is_present(bits, PresenceBits::LossLimit as i32)),
+        }
+    }
+}
+
+impl RithmicRiskLimits {
+    pub fn with_products(mut self, products: &[ResponseProductRmsInfo]) -> Self {
+        for info in products {
+            let limits = RithmicProductLimits::from(info);
+
+            self.products.insert(limits.product_code.clone(), limits);
+        }
+
+        self
+    }
+
+    pub fn apply_update(&mut self, update: &AccountRmsUpdates) {
+        if let Some(value) = update
+            .auto_liq_threshold_current_value
+            .as_ref()
+            .and_then(|v| v.parse::<f64>().ok())
+        {
+            self.auto_liquidate_threshold_current = Some(value);
+        }
+    }
+
+    /// Checks an order of signed `qty` (negative to sell) against the broker limits given the
+    /// current signed position in the product
+    pub fn check_order(&self, product_code: &str, qty: i32, position: i32) -> Result<(), String> {
+        let product = self.products.get(product_code);
+
+        let max_order_quantity = product
+            .and_then(|p| p.max_order_quantity)
+            .or(self.max_order_quantity);
+
+        if let Some(max) = max_order_quantity
+            && qty.abs() > max
+        {
+            return Err(format!(
+                "order quantity {} exceeds max order quantity {}",
+                qty.abs(),
+                max
+            ));
+        }
+
+        let resulting = position + qty;
+
+        if resulting > 0 {
+            let buy_limit = product.and_then(|p| p.buy_limit).or(self.buy_limit);
+
+            if let Some(limit) = buy_limit
+                && resulting > limit
+            {
+                return Err(format!("long position {} exceeds buy limit {}", resulting, limit));
+            }
+        } else if resulting < 0 {
+            let sell_limit = product.and_then(|p| p.sell_limit).or(self.sell_limit);
+
+            if let Some(limit) = sell_limit
+                && -resulting > limit
+            {
+                return Err(format!("short position {} exceeds sell limit {}", -resulting, limit));
+            }
+        }
+
+        Ok(())
+    }
+}
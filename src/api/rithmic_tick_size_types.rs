@@ -0,0 +1,249 @@
+use crate::rti::{ResponseGiveTickSizeTypeTable, response_give_tick_size_type_table};
+
+/// Tolerance of the price comparisons, prices are doubles on the wire
+const PRICE_EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RithmicRoundDirection {
+    Nearest,
+    Up,
+    Down,
+}
+
+/// Price band sharing one tick size, unbounded when a price is None
+#[derive(Debug, Clone, PartialEq)]
+pub struct RithmicTickBand {
+    pub first_price: Option<f64>,
+    pub first_inclusive: bool,
+    pub last_price: Option<f64>,
+    pub last_inclusive: bool,
+    pub tick_size: f64,
+}
+
+impl RithmicTickBand {
+    pub fn contains(&self, price: f64) -> bool {
+        let above_first = match self.first_price {
+            Some(first) if self.first_inclusive => price >= first - PRICE_EPSILON,
+            Some(first) => price > first + PRICE_EPSILON,
+            None => true,
+        };
+
+        let below_last = match self.last_price {
+            Some(last) if self.last_inclusive => price <= last + PRICE_EPSILON,
+            Some(last) => price < last - PRICE_EPSILON,
+            None => true,
+        };
+
+        above_first && below_last
+    }
+}
+
+impl From<&ResponseGiveTickSizeTypeTable> for RithmicTickBand {
+    fn from(row: &ResponseGiveTickSizeTypeTable) -> Self {
+        use response_give_tick_size_type_table::PresenceBits;
+
+        let bits = row.presence_bits;
+
+        RithmicTickBand {
+            first_price: row
+                .tick_size_first_price
+                .filter(|_| is_present(bits, PresenceBits::TickSizeFirstPrice as i32)),
+            first_inclusive: row
+                .tick_size_fp_operator
+                .as_deref()
+                .is_none_or(|operator| operator.contains('=')),
+            last_price: row
+                .tick_size_last_price
+                .filter(|_| is_present(bits, PresenceBits::TickSizeLastPrice as i32)),
+            last_inclusive: row
+                .tick_size_lp_operator
+                .as_deref()
+                .is_some_and(|operator| operator.contains('=')),
+            tick_size: row.min_fprice_change.unwrap_or_default(),
+        }
+    }
+}
+
+/// Tick sizes of an instrument by price band
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RithmicTickSizeTable {
+    /// None when the instrument has a single tick size
+    pub tick_size_type: Option<String>,
+    /// Bands ordered by price
+    pub bands: Vec<RithmicTickBand>,
+}
+
+impl RithmicTickSizeTable {
+    /// Table of an instrument with one tick size at every price
+    pub fn fixed(tick_size: f64) -> Self {
+        RithmicTickSizeTable {
+            tick_size_type: None,
+            bands: vec![RithmicTickBand {
+                first_price: None,
+                first_inclusive: true,
+                last_price: None,
+                last_inclusive: true,
+                tick_size,
+            }],
+        }
+    }
+
+    pub fn from_rows(tick_size_type: &str, rows: &[ResponseGiveTickSizeTypeTable]) -> Self {
+        let mut bands: Vec<RithmicTickBand> = rows
+            .iter()
+            .map(RithmicTickBand::from)
+            .filter(|band| band.tick_size > 0.0)
+            .collect();
+
+        bands.sort_by(|a, b| {
+            a.first_price
+                .unwrap_or(f64::NEG_INFINITY)
+                .total_cmp(&b.first_price.unwrap_or(f64::NEG_INFINITY))
+        });
+
+        RithmicTickSizeTable {
+            tick_size_type: Some(tick_size_type.to_string()),
+            bands,
+        }
+    }
+
+    pub fn band_at(&self, price: f64) -> Option<&RithmicTickBand> {
+        self.bands.iter().find(|band| band.contains(price))
+    }
+
+    pub fn tick_size_at(&self, price: f64) -> Option<f64> {
+        self.band_at(price).map(|band| band.tick_size)
+    }
+
+    /// Closest valid price in `direction`, None when no band covers `price`
+    pub fn round_to_tick(&self, price: f64, direction: RithmicRoundDirection) -> Option<f64> {
+        let band = self.band_at(price)?;
+
+        // Ticks start at the first price of their band
+        let anchor = band.first_price.unwrap_or_default();
+        let ticks = (price - anchor) / band.tick_size;
+
+        let ticks = match direction {
+            RithmicRoundDirection::Nearest => ticks.round(),
+            RithmicRoundDirection::Up => (ticks - PRICE_EPSILON).ceil(),
+            RithmicRoundDirection::Down => (ticks + PRICE_EPSILON).floor(),
+        };
+
+        Some(clean(anchor + ticks * band.tick_size))
+    }
+
+    pub fn is_valid_price(&self, price: f64) -> bool {
+        self.round_to_tick(price, RithmicRoundDirection::Nearest)
+            .is_some_and(|rounded| (rounded - price).abs() < PRICE_EPSILON)
+    }
+
+    /// Signed number of ticks from `from` to `to`, walking through the bands in between. None
+    /// when a price in between is not covered by the table.
+    pub fn ticks_between(&self, from: f64, to: f64) -> Option<i64> {
+        let (low, high) = if from <= to { (from, to) } else { (to, from) };
+
+        let mut ticks = 0.0;
+        let mut covered = 0.0;
+
+        for band in &self.bands {
+            let start = band.first_price.unwrap_or(f64::NEG_INFINITY).max(low);
+            let end = band.last_price.unwrap_or(f64::INFINITY).min(high);
+
+            if end > start {
+                ticks += (end - start) / band.tick_size;
+                covered += end - start;
+            }
+        }
+
+        if covered < high - low - PRICE_EPSILON {
+            return None;
+        }
+
+        let ticks = ticks.round() as i64;
+
+        Some(if from <= to { ticks } else { -ticks })
+    }
+}
+
+/// Drops the float noise of a price computed from ticks
+fn clean(price: f64) -> f64 {
+    (price * 1e9).round() / 1e9
+}
+
+fn is_present(presence_bits: Option<u32>, bit: i32) -> bool {
+    presence_bits.is_some_and(|bits| bits & bit as u32 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn band(first_price: Option<f64>, last_price: Option<f64>, tick_size: f64) -> RithmicTickBand {
+        RithmicTickBand {
+            first_price,
+            first_inclusive: true,
+            last_price,
+            last_inclusive: false,
+            tick_size,
+        }
+    }
+
+    /// 0.01 below 10, 0.05 up to 100 and 0.1 above
+    fn table() -> RithmicTickSizeTable {
+        RithmicTickSizeTable {
+            tick_size_type: Some("banded".to_string()),
+            bands: vec![
+                band(None, Some(10.0), 0.01),
+                band(Some(10.0), Some(100.0), 0.05),
+                band(Some(100.0), None, 0.1),
+            ],
+        }
+    }
+
+    #[test]
+    fn round_to_tick_uses_the_band_of_the_price() {
+        let table = table();
+
+        assert_eq!(table.round_to_tick(9.996, RithmicRoundDirection::Nearest), Some(10.0));
+        assert_eq!(table.round_to_tick(12.03, RithmicRoundDirection::Nearest), Some(12.05));
+        assert_eq!(table.round_to_tick(12.01, RithmicRoundDirection::Up), Some(12.05));
+        assert_eq!(table.round_to_tick(12.04, RithmicRoundDirection::Down), Some(12.0));
+        assert_eq!(table.round_to_tick(150.04, RithmicRoundDirection::Up), Some(150.1));
+        assert_eq!(table.round_to_tick(150.04, RithmicRoundDirection::Down), Some(150.0));
+    }
+
+    #[test]
+    fn round_to_tick_keeps_valid_prices() {
+        let table = table();
+
+        for price in [9.99, 10.0, 12.05, 100.0, 150.1] {
+            assert_eq!(table.round_to_tick(price, RithmicRoundDirection::Up), Some(price));
+            assert_eq!(table.round_to_tick(price, RithmicRoundDirection::Down), Some(price));
+            assert!(table.is_valid_price(price));
+        }
+
+        assert!(!table.is_valid_price(12.03));
+    }
+
+    #[test]
+    fn ticks_between_walks_through_the_bands() {
+        let table = table();
+
+        assert_eq!(table.ticks_between(9.9, 10.2), Some(14));
+        assert_eq!(table.ticks_between(10.2, 9.9), Some(-14));
+        assert_eq!(table.ticks_between(9.99, 100.5), Some(1 + 1800 + 5));
+        assert_eq!(table.ticks_between(12.05, 12.05), Some(0));
+    }
+
+    #[test]
+    fn ticks_between_fails_outside_the_table() {
+        let table = RithmicTickSizeTable {
+            tick_size_type: Some("banded".to_string()),
+            bands: vec![band(Some(10.0), Some(100.0), 0.05)],
+        };
+
+        assert_eq!(table.ticks_between(5.0, 20.0), None);
+        assert_eq!(table.round_to_tick(5.0, RithmicRoundDirection::Nearest), None);
+        assert_eq!(RithmicTickSizeTable::fixed(0.25).ticks_between(-1.0, 1.0), Some(8));
+    }
+}
@@ -7,7 +7,9 @@ use crate::{
         request_login::SysInfraType,
     },
 };
-use super::rithmic_command_types::RithmicBracketOrder;
+use super::rithmic_command_types::{
+    RithmicAccount, RithmicBracketOrder, RithmicMarketDataUsageCapacity,
+};
 
 pub const TRADE_ROUTE_LIVE: &str = "globex";
 pub const TRADE_ROUTE_DEMO: &str = "simulator";
@@ -33,6 +35,13 @@ impl RithmicSenderApi {
         }
     }
 
+    /// Account used by the order, pnl and rms requests
+    pub fn set_account(&mut self, account: &RithmicAccount) {
+        self.account_id = account.account_id.clone();
+        self.fcm_id = account.fcm_id.clone();
+        self.ib_id = account.ib_id.clone();
+    }
+
     fn get_next_message_id(&mut self) -> String {
         self.message_id_counter += 1;
         self.message_id_counter.to_string()
@@ -49,12 +58,20 @@ impl RithmicSenderApi {
         (Bytes::from(buf), id)
     }
 
-    pub fn request_get_instrument_by_underlying(&mut self) -> (Bytes, String) {
+    pub fn request_get_instrument_by_underlying(
+        &mut self,
+        underlying_symbol: &str,
+        exchange: &str,
+        expiration_date: Option<String>,
+    ) -> (Bytes, String) {
         let id = self.get_next_message_id();
 
         let req = RequestGetInstrumentByUnderlying {
-            template_id: 103,
-            ..RequestGetInstrumentByUnderlying::default()
+            template_id: 102,
+            user_msg: vec![id.clone()],
+            underlying_symbol: Some(underlying_symbol.into()),
+            exchange: Some(exchange.into()),
+            expiration_date,
         };
 
         self.request_to_buf(req, id)
@@ -86,12 +103,19 @@ impl RithmicSenderApi {
             template_version: Some("5.27".into()),
             user: Some(user.to_string()),
             password: Some(password.to_string()),
-            app_name: Some("pede:pts".to_string()),
-            app_version: Some("1".into()),
+            app_name: Some(self.conn_info.app_name.clone()),
+            app_version: Some(self.conn_info.app_version.clone()),
             system_name: Some(system_name.to_string()),
             infra_type: Some(infra_type.into()),
             user_msg: vec![id.clone()],
-            ..RequestLogin::default()
+            mac_addr: self.conn_info.mac_addr.clone(),
+            os_version: self.conn_info.os_version.clone(),
+            os_platform: self.conn_info.os_platform.clone(),
+            aggregated_quotes: if infra_type == SysInfraType::TickerPlant {
+                self.conn_info.aggregated_quotes
+            } else {
+                None
+            },
         };
 
         self.request_to_buf(req, id)
@@ -137,6 +161,47 @@ impl RithmicSenderApi {
         self.request_to_buf(req, id)
     }
 
+    pub fn request_market_data_update_by_underlying(
+        &mut self,
+        underlying_symbol: &str,
+        exchange: &str,
+        expiration_date: Option<String>,
+        fields: Vec<request_market_data_update_by_underlying::UpdateBits>,
+        request_type: request_market_data_update_by_underlying::Request,
+    ) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let mut bits = 0;
+
+        for field in fields {
+            bits |= field as u32;
+        }
+
+        let req = RequestMarketDataUpdateByUnderlying {
+            template_id: 105,
+            user_msg: vec![id.clone()],
+            underlying_symbol: Some(underlying_symbol.into()),
+            exchange: Some(exchange.into()),
+            expiration_date,
+            request: Some(request_type.into()),
+            update_bits: Some(bits),
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_give_tick_size_type_table(&mut self, tick_size_type: &str) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestGiveTickSizeTypeTable {
+            template_id: 107,
+            user_msg: vec![id.clone()],
+            tick_size_type: Some(tick_size_type.into()),
+        };
+
+        self.request_to_buf(req, id)
+    }
+
     pub fn request_product_codes(&mut self, exchange: Option<String>) -> (Bytes, String) {
         let id = self.get_next_message_id();
 
@@ -162,6 +227,32 @@ impl RithmicSenderApi {
         self.request_to_buf(req, id)
     }
 
+    pub fn request_get_volume_at_price(&mut self, symbol: &str, exchange: &str) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestGetVolumeAtPrice {
+            template_id: 119,
+            user_msg: vec![id.clone()],
+            symbol: Some(symbol.into()),
+            exchange: Some(exchange.into()),
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_auxilliary_reference_data(&mut self, symbol: &str, exchange: &str) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestAuxilliaryReferenceData {
+            template_id: 121,
+            user_msg: vec![id.clone()],
+            symbol: Some(symbol.into()),
+            exchange: Some(exchange.into()),
+        };
+
+        self.request_to_buf(req, id)
+    }
+
     pub fn request_rithmic_system_gateway_info(&mut self, system_name: String) -> (Bytes, String) {
         let id = self.get_next_message_id();
 
@@ -206,6 +297,64 @@ impl RithmicSenderApi {
         self.request_to_buf(req, id)
     }
 
+    pub fn request_account_list(&mut self) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestAccountList {
+            template_id: 302,
+            fcm_id: Some(self.fcm_id.clone()),
+            ib_id: Some(self.ib_id.clone()),
+            user_type: Some(USER_TYPE),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_account_rms_info(&mut self) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestAccountRmsInfo {
+            template_id: 304,
+            fcm_id: Some(self.fcm_id.clone()),
+            ib_id: Some(self.ib_id.clone()),
+            user_type: Some(USER_TYPE),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_product_rms_info(&mut self, account_id: &str) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestProductRmsInfo {
+            template_id: 306,
+            fcm_id: Some(self.fcm_id.clone()),
+            ib_id: Some(self.ib_id.clone()),
+            account_id: Some(account_id.into()),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_account_rms_updates(&mut self, subscribe: bool) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestAccountRmsUpdates {
+            template_id: 3508,
+            fcm_id: Some(self.fcm_id.clone()),
+            ib_id: Some(self.ib_id.clone()),
+            account_id: Some(self.account_id.clone()),
+            request: Some(if subscribe { "subscribe" } else { "unsubscribe" }.into()),
+            update_bits: Some(request_account_rms_updates::UpdateBits::AutoLiqThresholdCurrentValue.into()),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
     pub fn request_subscribe_for_order_updates(&mut self) -> (Bytes, String) {
         let id = self.get_next_message_id();
 
@@ -260,6 +409,7 @@ impl RithmicSenderApi {
             finish_index: Some(finish_index),
             direction: Some(direction.into()),
             time_order: Some(time_order.into()),
+            resume_bars: Some(true),
             ..RequestTickBarReplay::default()
         };
 
@@ -316,6 +466,7 @@ impl RithmicSenderApi {
             finish_index: Some(finish_index),
             direction: Some(direction.into()),
             time_order: Some(time_order.into()),
+            resume_bars: Some(true),
             ..RequestTimeBarReplay::default()
         };
 
@@ -346,6 +497,46 @@ impl RithmicSenderApi {
         self.request_to_buf(req, id)
     }
 
+    /// Minute bars with their volume profile, `resume_bars` lets a truncated replay be resumed
+    /// with `request_resume_bars`
+    pub fn request_volume_profile_minute_bars(
+        &mut self,
+        symbol: &str,
+        exchange: &str,
+        bar_type_period: i32,
+        start_index: i32,
+        finish_index: i32,
+        user_max_count: Option<i32>,
+    ) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestVolumeProfileMinuteBars {
+            template_id: 208,
+            user_msg: vec![id.clone()],
+            symbol: Some(symbol.into()),
+            exchange: Some(exchange.into()),
+            bar_type_period: Some(bar_type_period),
+            start_index: Some(start_index),
+            finish_index: Some(finish_index),
+            user_max_count,
+            resume_bars: Some(true),
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_resume_bars(&mut self, request_key: &str) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestResumeBars {
+            template_id: 210,
+            user_msg: vec![id.clone()],
+            request_key: Some(request_key.into()),
+        };
+
+        self.request_to_buf(req, id)
+    }
+
     pub fn request_new_order(
         &mut self,
         exchange: &str,
@@ -399,6 +590,22 @@ impl RithmicSenderApi {
         // TODO
         let trade_route = "";
 
+        let bracket_type = bracket_order
+            .bracket_type
+            .unwrap_or(request_bracket_order::BracketType::TargetAndStopStatic.into());
+
+        let has_target = !matches!(
+            request_bracket_order::BracketType::try_from(bracket_type),
+            Ok(request_bracket_order::BracketType::StopOnly)
+                | Ok(request_bracket_order::BracketType::StopOnlyStatic)
+        );
+
+        let has_stop = !matches!(
+            request_bracket_order::BracketType::try_from(bracket_type),
+            Ok(request_bracket_order::BracketType::TargetOnly)
+                | Ok(request_bracket_order::BracketType::TargetOnlyStatic)
+        );
+
         let req = RequestBracketOrder {
             template_id: 330,
             fcm_id: Some(self.fcm_id.clone()),
@@ -413,12 +620,22 @@ impl RithmicSenderApi {
             price_type: Some(bracket_order.ordertype),
             manual_or_auto: Some(2),
             duration: Some(bracket_order.duration),
-            bracket_type: Some(6),
-            target_quantity: Some(bracket_order.qty),
-            stop_quantity: Some(bracket_order.qty),
-            target_ticks: Some(bracket_order.profit_ticks),
-            stop_ticks: Some(bracket_order.stop_ticks),
-            price: if bracket_order.ordertype != request_bracket_order::PriceType::Market.into() {
+            bracket_type: Some(bracket_type),
+            target_quantity: has_target.then(|| bracket_order.target_qty.unwrap_or(bracket_order.qty)),
+            stop_quantity: has_stop.then(|| bracket_order.stop_qty.unwrap_or(bracket_order.qty)),
+            target_ticks: has_target.then_some(bracket_order.profit_ticks),
+            stop_ticks: has_stop.then_some(bracket_order.stop_ticks),
+            trailing_stop_trigger_ticks: bracket_order
+                .trailing_stop
+                .as_ref()
+                .map(|t| t.trigger_ticks),
+            trailing_stop_by_last_trade_price: bracket_order
+                .trailing_stop
+                .as_ref()
+                .map(|t| t.by_last_trade_price),
+            break_even_ticks: bracket_order.break_even.as_ref().map(|b| b.ticks),
+            break_even_trigger_ticks: bracket_order.break_even.as_ref().map(|b| b.trigger_ticks),
+            price: if bracket_order.ordertype != request_bracket_order::PriceType::Market as i32 {
                 bracket_order.price
             } else {
                 None
@@ -500,6 +717,26 @@ impl RithmicSenderApi {
         self.request_to_buf(req, id)
     }
 
+    pub fn request_replay_executions(
+        &mut self,
+        start_index: i32,
+        finish_index: i32,
+    ) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestReplayExecutions {
+            template_id: 3506,
+            fcm_id: Some(self.fcm_id.clone()),
+            ib_id: Some(self.ib_id.clone()),
+            account_id: Some(self.account_id.clone()),
+            start_index: Some(start_index),
+            finish_index: Some(finish_index),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
     pub fn request_update_target_bracket_level(
         &mut self,
         basket_id: &str,
@@ -584,6 +821,67 @@ impl RithmicSenderApi {
         self.request_to_buf(req, id)
     }
 
+    pub fn request_show_order_history_dates(&mut self) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestShowOrderHistoryDates {
+            template_id: 318,
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_show_order_history(&mut self, basket_id: Option<String>) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestShowOrderHistory {
+            template_id: 322,
+            fcm_id: Some(self.fcm_id.clone()),
+            ib_id: Some(self.ib_id.clone()),
+            account_id: Some(self.account_id.clone()),
+            basket_id,
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_show_order_history_summary(&mut self, date: &str) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestShowOrderHistorySummary {
+            template_id: 324,
+            fcm_id: Some(self.fcm_id.clone()),
+            ib_id: Some(self.ib_id.clone()),
+            account_id: Some(self.account_id.clone()),
+            date: Some(date.into()),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_show_order_history_detail(
+        &mut self,
+        basket_id: &str,
+        date: &str,
+    ) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestShowOrderHistoryDetail {
+            template_id: 326,
+            fcm_id: Some(self.fcm_id.clone()),
+            ib_id: Some(self.ib_id.clone()),
+            account_id: Some(self.account_id.clone()),
+            basket_id: Some(basket_id.into()),
+            date: Some(date.into()),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
     pub fn request_pnl_position_updates(
         &mut self,
         action: request_pn_l_position_updates::Request,
@@ -615,4 +913,72 @@ impl RithmicSenderApi {
 
         self.request_to_buf(req, id)
     }
+
+    pub fn request_list_unaccepted_agreements(&mut self) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestListUnacceptedAgreements {
+            template_id: 500,
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_list_accepted_agreements(&mut self) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestListAcceptedAgreements {
+            template_id: 502,
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_accept_agreement(
+        &mut self,
+        agreement_id: &str,
+        market_data_usage_capacity: Option<RithmicMarketDataUsageCapacity>,
+    ) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestAcceptAgreement {
+            template_id: 504,
+            agreement_id: Some(agreement_id.into()),
+            market_data_usage_capacity: market_data_usage_capacity.map(|c| c.as_str().into()),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_show_agreement(&mut self, agreement_id: &str) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestShowAgreement {
+            template_id: 506,
+            agreement_id: Some(agreement_id.into()),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
+
+    pub fn request_set_rithmic_mrkt_data_self_cert_status(
+        &mut self,
+        agreement_id: &str,
+        market_data_usage_capacity: RithmicMarketDataUsageCapacity,
+    ) -> (Bytes, String) {
+        let id = self.get_next_message_id();
+
+        let req = RequestSetRithmicMrktDataSelfCertStatus {
+            template_id: 508,
+            agreement_id: Some(agreement_id.into()),
+            market_data_usage_capacity: Some(market_data_usage_capacity.as_str().into()),
+            user_msg: vec![id.clone()],
+        };
+
+        self.request_to_buf(req, id)
+    }
 }
@@ -0,0 +1,513 @@
+use std::{path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::{task::JoinHandle, time::timeout};
+use tracing::{event, Level};
+
+use crate::{
+    api::{
+        DEFAULT_RTI_WS_URL, RithmicConnectionInfo, receiver_api::RithmicResponse,
+        rithmic_command_types::RithmicAccount,
+    },
+    plants::{
+        history_plant::{HistoryPlant, RithmicHistoryPlant, RithmicHistoryPlantHandle},
+        order_plant::{OrderPlant, RithmicOrderPlant, RithmicOrderPlantHandle},
+        plant::{PlantExtension, RithmicPlant},
+        pnl_plant::{PnlPlant, RithmicPnlPlant, RithmicPnlPlantHandle},
+        repository_plant::{RepositoryPlant, RithmicRepositoryPlant, RithmicRepositoryPlantHandle},
+        shared_plant::{RithmicGatewayLatency, RithmicSharedPlant},
+        ticker_plant::{RithmicTickerPlant, RithmicTickerPlantHandle, TickerPlant},
+    },
+    proxy::RithmicProxyConfig,
+    tls::RithmicTlsConfig,
+    rti::messages::RithmicMessage,
+    ws::RithmicStream,
+};
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RithmicPlantType {
+    Ticker,
+    Order,
+    Pnl,
+    History,
+    /// Agreements and market data self-certification, not part of `all`
+    Repository,
+}
+
+impl RithmicPlantType {
+    pub fn all() -> Vec<RithmicPlantType> {
+        vec![
+            RithmicPlantType::Ticker,
+            RithmicPlantType::Order,
+            RithmicPlantType::Pnl,
+            RithmicPlantType::History,
+        ]
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RithmicClientConfig {
+    pub connection: RithmicConnectionInfo,
+    /// Gateway the plants connect to, `connection.url` is used as is when None
+    pub gateway_name: Option<String>,
+    /// Url used to look the gateway up, defaults to `DEFAULT_RTI_WS_URL`
+    #[serde(default)]
+    pub bootstrap_url: Option<String>,
+    /// Probe every gateway of the system and connect through the fastest one, falling back to
+    /// the next one when the connection fails. `gateway_name`, when set, is tried first.
+    #[serde(default)]
+    pub auto_gateway: bool,
+    pub plants: Vec<RithmicPlantType>,
+    /// Account used by the order and pnl plants, defaults to the first account of the user
+    pub account_id: Option<String>,
+    /// File the ticker plant keeps its instruments in between runs
+    #[serde(default)]
+    pub instrument_cache_file: Option<PathBuf>,
+}
+
+impl RithmicClientConfig {
+    /// Config connecting every plant through `gateway_name`
+    pub fn new(connection: RithmicConnectionInfo, gateway_name: &str) -> RithmicClientConfig {
+        RithmicClientConfig {
+            connection,
+            gateway_name: Some(gateway_name.to_string()),
+            bootstrap_url: None,
+            auto_gateway: false,
+            plants: RithmicPlantType::all(),
+            account_id: None,
+            instrument_cache_file: None,
+        }
+    }
+
+    pub fn with_plants(mut self, plants: &[RithmicPlantType]) -> Self {
+        self.plants = plants.to_vec();
+        self
+    }
+
+    /// Connect through the fastest gateway, see `auto_gateway`
+    pub fn with_auto_gateway(mut self) -> Self {
+        self.auto_gateway = true;
+        self
+    }
+
+    pub fn with_account(mut self, account_id: &str) -> Self {
+        self.account_id = Some(account_id.to_string());
+        self
+    }
+
+    pub fn with_instrument_cache_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.instrument_cache_file = Some(path.into());
+        self
+    }
+
+    fn has_plant(&self, plant: RithmicPlantType) -> bool {
+        self.plants.contains(&plant)
+    }
+}
+
+/// Owns the plants of a Rithmic session.
+///
+/// `connect` resolves the gateway, logs into the requested plants concurrently and shares the
+/// account between the order and pnl plants. `shutdown` logs every plant out.
+///
+/// ```ignore
+/// let config = RithmicClientConfig::new(conn_info, "Chicago Area")
+///     .with_plants(&[RithmicPlantType::Ticker, RithmicPlantType::Order]);
+///
+/// let mut client = RithmicClient::connect(&config).await?;
+/// let ticker_plant = client.ticker_plant().unwrap();
+/// ...
+/// client.shutdown().await?;
+/// ```
+pub struct RithmicClient {
+    account: Option<RithmicAccount>,
+    history_plant: Option<RithmicHistoryPlant>,
+    order_plant: Option<RithmicOrderPlant>,
+    pnl_plant: Option<RithmicPnlPlant>,
+    repository_plant: Option<RithmicRepositoryPlant>,
+    ticker_plant: Option<RithmicTickerPlant>,
+}
+
+impl RithmicClient {
+    /// Connects through the configured gateway, or through every gateway from fastest to slowest
+    /// until one succeeds when `auto_gateway` is set
+    pub async fn connect(config: &RithmicClientConfig) -> Result<RithmicClient, String> {
+        let bootstrap_url = config.bootstrap_url.as_deref().unwrap_or(DEFAULT_RTI_WS_URL);
+        let system_name = &config.connection.system_name;
+        let proxy = config.connection.proxy.as_ref();
+        let tls = config.connection.tls.as_ref();
+
+        let urls = if config.auto_gateway {
+            ranked_gateway_urls(bootstrap_url, proxy, tls, system_name, config.gateway_name.as_deref())
+                .await?
+        } else if let Some(gateway_name) = &config.gateway_name {
+            vec![resolve_gateway(bootstrap_url, proxy, tls, system_name, gateway_name).await?]
+        } else {
+            vec![config.connection.url.clone()]
+        };
+
+        let mut last_error = format!("no gateway available for {}", system_name);
+
+        for url in urls {
+            match RithmicClient::connect_to(config, &url).await {
+                Ok(client) => return Ok(client),
+                Err(e) => {
+                    event!(Level::WARN, "client: connection through {} failed: {}", url, e);
+
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    async fn connect_to(config: &RithmicClientConfig, url: &str) -> Result<RithmicClient, String> {
+        let conn_info = RithmicConnectionInfo {
+            url: url.to_string(),
+            ..config.connection.clone()
+        };
+
+        event!(Level::INFO, "client: connecting to {}", conn_info.url);
+
+        let ticker_plant = match &config.instrument_cache_file {
+            Some(path) => TickerPlant::with_instrument_cache_file(path),
+            None => TickerPlant::default(),
+        };
+
+        let (ticker, order, pnl, history, repository) = tokio::join!(
+            start_plant(config, &conn_info, RithmicPlantType::Ticker, ticker_plant),
+            start_plant(config, &conn_info, RithmicPlantType::Order, OrderPlant::default()),
+            start_plant(config, &conn_info, RithmicPlantType::Pnl, PnlPlant),
+            start_plant(config, &conn_info, RithmicPlantType::History, HistoryPlant),
+            start_plant(config, &conn_info, RithmicPlantType::Repository, RepositoryPlant),
+        );
+
+        let errors: Vec<String> = [
+            ticker.as_ref().err(),
+            order.as_ref().err(),
+            pnl.as_ref().err(),
+            history.as_ref().err(),
+            repository.as_ref().err(),
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+
+        let order_login = order.as_ref().ok().and_then(|o| o.as_ref()).map(|(_, r)| r.clone());
+        let pnl_login = pnl.as_ref().ok().and_then(|p| p.as_ref()).map(|(_, r)| r.clone());
+
+        let mut client = RithmicClient {
+            account: None,
+            history_plant: history.ok().flatten().map(|(plant, _)| plant),
+            order_plant: order.ok().flatten().map(|(plant, _)| plant),
+            pnl_plant: pnl.ok().flatten().map(|(plant, _)| plant),
+            repository_plant: repository.ok().flatten().map(|(plant, _)| plant),
+            ticker_plant: ticker.ok().flatten().map(|(plant, _)| plant),
+        };
+
+        if !errors.is_empty() {
+            let _ = client.shutdown().await;
+
+            return Err(errors.join("; "));
+        }
+
+        let login = order_login.or(pnl_login);
+
+        match client.resolve_account(config, login).await {
+            Ok(account) => client.account = account,
+            Err(e) => {
+                let _ = client.shutdown().await;
+
+                return Err(e);
+            }
+        }
+
+        if let Some(account) = &client.account {
+            event!(Level::INFO, "client: using account {}", account.account_id);
+
+            if let Some(order_plant) = client.order_plant() {
+                order_plant.set_account(account).await;
+            }
+
+            if let Some(pnl_plant) = client.pnl_plant() {
+                pnl_plant.set_account(account).await;
+            }
+        }
+
+        Ok(client)
+    }
+
+    /// Picks the account from the order plant account list, or from the config when only the
+    /// pnl plant is used
+    async fn resolve_account(
+        &self,
+        config: &RithmicClientConfig,
+        login: Option<RithmicResponse>,
+    ) -> Result<Option<RithmicAccount>, String> {
+        let Some(RithmicMessage::ResponseLogin(login)) = login.map(|r| r.message) else {
+            return Ok(None);
+        };
+
+        let mut account = RithmicAccount {
+            account_id: config.account_id.clone().unwrap_or_default(),
+            fcm_id: login.fcm_id.unwrap_or_default(),
+            ib_id: login.ib_id.unwrap_or_default(),
+        };
+
+        let Some(order_plant) = self.order_plant() else {
+            return Ok(config.account_id.as_ref().map(|_| account));
+        };
+
+        order_plant.set_account(&account).await;
+
+        let accounts = order_plant.account_list().await?;
+
+        let selected = match &config.account_id {
+            Some(account_id) => accounts
+                .iter()
+                .find(|a| a.account_id.as_ref() == Some(account_id))
+                .ok_or_else(|| format!("account {} not found", account_id))?,
+            None => accounts
+                .first()
+                .ok_or_else(|| "no account available for this user".to_string())?,
+        };
+
+        account.account_id = selected.account_id.clone().unwrap_or_default();
+
+        if let Some(fcm_id) = &selected.fcm_id {
+            account.fcm_id = fcm_id.clone();
+        }
+
+        if let Some(ib_id) = &selected.ib_id {
+            account.ib_id = ib_id.clone();
+        }
+
+        Ok(Some(account))
+    }
+
+    pub fn account(&self) -> Option<&RithmicAccount> {
+        self.account.as_ref()
+    }
+
+    pub fn ticker_plant(&self) -> Option<RithmicTickerPlantHandle> {
+        self.ticker_plant.as_ref().map(|p| p.get_handle())
+    }
+
+    pub fn order_plant(&self) -> Option<RithmicOrderPlantHandle> {
+        self.order_plant.as_ref().map(|p| p.get_handle())
+    }
+
+    pub fn pnl_plant(&self) -> Option<RithmicPnlPlantHandle> {
+        self.pnl_plant.as_ref().map(|p| p.get_handle())
+    }
+
+    pub fn history_plant(&self) -> Option<RithmicHistoryPlantHandle> {
+        self.history_plant.as_ref().map(|p| p.get_handle())
+    }
+
+    pub fn repository_plant(&self) -> Option<RithmicRepositoryPlantHandle> {
+        self.repository_plant.as_ref().map(|p| p.get_handle())
+    }
+
+    /// Logs every plant out concurrently and waits for their connections to close
+    pub async fn shutdown(&mut self) -> Result<(), String> {
+        let (ticker, order, pnl, history, repository) = tokio::join!(
+            stop_plant(self.ticker_plant.take()),
+            stop_plant(self.order_plant.take()),
+            stop_plant(self.pnl_plant.take()),
+            stop_plant(self.history_plant.take()),
+            stop_plant(self.repository_plant.take()),
+        );
+
+        let errors: Vec<String> = [ticker, order, pnl, history, repository]
+            .into_iter()
+            .filter_map(Result::err)
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+/// Url of `gateway_name` in `system_name`, looked up through `bootstrap_url`
+pub async fn resolve_gateway(
+    bootstrap_url: &str,
+    proxy: Option<&RithmicProxyConfig>,
+    tls: Option<&RithmicTlsConfig>,
+    system_name: &str,
+    gateway_name: &str,
+) -> Result<String, String> {
+    let mut shared_plant = RithmicSharedPlant::with_url(bootstrap_url)
+        .with_proxy(proxy.cloned())
+        .with_tls(tls.cloned());
+
+    let result = find_gateway(&mut shared_plant, system_name, gateway_name).await;
+
+    if let Err(e) = shared_plant.close().await {
+        event!(Level::WARN, "client: failed to close shared plant: {}", e);
+    }
+
+    result
+}
+
+async fn find_gateway(
+    shared_plant: &mut RithmicSharedPlant,
+    system_name: &str,
+    gateway_name: &str,
+) -> Result<String, String> {
+    check_system(shared_plant, system_name).await?;
+
+    let gateways = shared_plant
+        .rithmic_system_gateway_info(system_name.to_string())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    gateways
+        .gateway_name
+        .iter()
+        .position(|g| g == gateway_name)
+        .and_then(|i| gateways.gateway_uri.get(i).cloned())
+        .ok_or_else(|| {
+            format!(
+                "unknown gateway {} for {}, available gateways: {:?}",
+                gateway_name, system_name, gateways.gateway_name
+            )
+        })
+}
+
+/// Urls of the gateways of `system_name` that answered a probe, `preferred` first and then from
+/// fastest to slowest
+pub async fn ranked_gateway_urls(
+    bootstrap_url: &str,
+    proxy: Option<&RithmicProxyConfig>,
+    tls: Option<&RithmicTlsConfig>,
+    system_name: &str,
+    preferred: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut shared_plant = RithmicSharedPlant::with_url(bootstrap_url)
+        .with_proxy(proxy.cloned())
+        .with_tls(tls.cloned());
+
+    let result = rank_gateways(&mut shared_plant, system_name).await;
+
+    if let Err(e) = shared_plant.close().await {
+        event!(Level::WARN, "client: failed to close shared plant: {}", e);
+    }
+
+    let mut ranked = result?;
+
+    if let Some(position) = ranked.iter().position(|l| Some(l.gateway_name.as_str()) == preferred) {
+        let latency = ranked.remove(position);
+
+        ranked.insert(0, latency);
+    }
+
+    for latency in &ranked {
+        event!(
+            Level::INFO,
+            "client: gateway {} handshake {:?} heartbeat {:?}",
+            latency.gateway_name,
+            latency.handshake,
+            latency.heartbeat
+        );
+    }
+
+    Ok(ranked.into_iter().map(|l| l.url).collect())
+}
+
+async fn rank_gateways(
+    shared_plant: &mut RithmicSharedPlant,
+    system_name: &str,
+) -> Result<Vec<RithmicGatewayLatency>, String> {
+    check_system(shared_plant, system_name).await?;
+
+    shared_plant
+        .ranked_gateways(system_name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn check_system(shared_plant: &mut RithmicSharedPlant, system_name: &str) -> Result<(), String> {
+    let systems = shared_plant
+        .rithmic_system_info()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if systems.system_name.iter().any(|s| s == system_name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown system {}, available systems: {:?}",
+            system_name, systems.system_name
+        ))
+    }
+}
+
+/// Connects and logs into the plant when `plant_type` is part of the config
+async fn start_plant<E: PlantExtension>(
+    config: &RithmicClientConfig,
+    conn_info: &RithmicConnectionInfo,
+    plant_type: RithmicPlantType,
+    extension: E,
+) -> Result<Option<(RithmicPlant<E>, RithmicResponse)>, String> {
+    if !config.has_plant(plant_type) {
+        return Ok(None);
+    }
+
+    let plant = RithmicPlant::spawn(conn_info, extension)
+        .await
+        .map_err(|e| format!("{}: {}", E::NAME, e))?;
+
+    let login = timeout(SHUTDOWN_TIMEOUT, plant.get_handle().login())
+        .await
+        .unwrap_or_else(|_| Err(format!("no answer within {:?}", SHUTDOWN_TIMEOUT)));
+
+    match login {
+        Ok(response) => Ok(Some((plant, response))),
+        Err(e) => Err(abort_plant(plant.connection_handle, E::NAME, e)),
+    }
+}
+
+/// Logs the plant out and waits for its connection to close
+async fn stop_plant<E: PlantExtension>(plant: Option<RithmicPlant<E>>) -> Result<(), String> {
+    let Some(plant) = plant else {
+        return Ok(());
+    };
+
+    let Ok(result) = timeout(SHUTDOWN_TIMEOUT, plant.get_handle().disconnect()).await else {
+        event!(Level::WARN, "client: {} did not log out in time", E::NAME);
+
+        plant.connection_handle.abort();
+
+        return Err(format!("{} logout: no answer within {:?}", E::NAME, SHUTDOWN_TIMEOUT));
+    };
+
+    wait_plant(plant.connection_handle, E::NAME).await;
+
+    result.map(|_| ())
+}
+
+fn abort_plant(connection_handle: JoinHandle<()>, plant: &str, error: String) -> String {
+    event!(Level::ERROR, "client: {} login failed: {}", plant, error);
+
+    connection_handle.abort();
+
+    format!("{} login failed: {}", plant, error)
+}
+
+async fn wait_plant(connection_handle: JoinHandle<()>, plant: &str) {
+    let abort_handle = connection_handle.abort_handle();
+
+    if timeout(SHUTDOWN_TIMEOUT, connection_handle).await.is_err() {
+        event!(Level::WARN, "client: {} did not close in time", plant);
+
+        abort_handle.abort();
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, env, fs, path::{Path, PathBuf}};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    api::RithmicConnectionInfo,
+    client::{RithmicClientConfig, RithmicPlantType},
+    proxy::RithmicProxyConfig,
+};
+
+/// One connection in a config file.
+///
+/// The password is read from `password_env` or `password_file` so it never has to be written in
+/// the config itself.
+///
+/// ```toml
+/// system_name = "Rithmic Paper Trading"
+/// gateway_name = "Chicago Area"
+/// user = "xxxxxxxx"
+/// password_env = "RITHMIC_PAPER_PASSWORD"
+/// app_name = "myapp:trader"
+/// app_version = "1.0"
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RithmicProfile {
+    #[serde(flatten)]
+    pub connection: RithmicConnectionInfo,
+    /// Environment variable holding the password
+    #[serde(default)]
+    pub password_env: Option<String>,
+    /// File holding the password
+    #[serde(default)]
+    pub password_file: Option<String>,
+    #[serde(default)]
+    pub gateway_name: Option<String>,
+    /// Url used to look the gateway up
+    #[serde(default)]
+    pub bootstrap_url: Option<String>,
+    /// Connect through the fastest gateway
+    #[serde(default)]
+    pub auto_gateway: bool,
+    #[serde(default)]
+    pub account_id: Option<String>,
+    #[serde(default = "RithmicPlantType::all")]
+    pub plants: Vec<RithmicPlantType>,
+    /// File the ticker plant keeps its instruments in between runs
+    #[serde(default)]
+    pub instrument_cache_file: Option<PathBuf>,
+}
+
+/// Several named connections, e.g. paper and live or one per FCM.
+///
+/// ```toml
+/// default_profile = "paper"
+///
+/// [profiles.paper]
+/// system_name = "Rithmic Paper Trading"
+/// gateway_name = "Chicago Area"
+/// user = "xxxxxxxx"
+/// password_env = "RITHMIC_PAPER_PASSWORD"
+///
+/// [profiles.live]
+/// system_name = "Rithmic 01"
+/// gateway_name = "Chicago Area"
+/// user = "yyyyyyyy"
+/// password_file = "/run/secrets/rithmic_live"
+/// plants = ["Order", "Pnl"]
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RithmicProfiles {
+    #[serde(default)]
+    pub default_profile: Option<String>,
+    pub profiles: HashMap<String, RithmicProfile>,
+}
+
+impl RithmicConnectionInfo {
+    /// Reads the connection from `RITHMIC_*` environment variables, loading `.env` first.
+    ///
+    /// `RITHMIC_USER` and `RITHMIC_SYSTEM_NAME` are required, the password comes from
+    /// `RITHMIC_PASSWORD` or the file named by `RITHMIC_PASSWORD_FILE`. `RITHMIC_URL`,
+    /// `RITHMIC_APP_NAME`, `RITHMIC_APP_VERSION`, `RITHMIC_AGGREGATED_QUOTES` and `RITHMIC_PROXY`
+    /// (a proxy url, see `RithmicProxyConfig::from_url`) are optional.
+    pub fn from_env() -> Result<RithmicConnectionInfo, String> {
+        dotenv::dotenv().ok();
+
+        let defaults = RithmicConnectionInfo::default();
+
+        let password = match env::var("RITHMIC_PASSWORD") {
+            Ok(password) => password,
+            Err(_) => read_password_file(&required_var("RITHMIC_PASSWORD_FILE")?)?,
+        };
+
+        let aggregated_quotes = match env::var("RITHMIC_AGGREGATED_QUOTES") {
+            Ok(value) => Some(
+                value
+                    .parse::<bool>()
+                    .map_err(|e| format!("invalid RITHMIC_AGGREGATED_QUOTES: {}", e))?,
+            ),
+            Err(_) => None,
+        };
+
+        let proxy = match env::var("RITHMIC_PROXY") {
+            Ok(url) => Some(
+                RithmicProxyConfig::from_url(&url)
+                    .map_err(|e| format!("invalid RITHMIC_PROXY: {}", e))?,
+            ),
+            Err(_) => None,
+        };
+
+        Ok(RithmicConnectionInfo {
+            url: env::var("RITHMIC_URL").unwrap_or(defaults.url),
+            user: required_var("RITHMIC_USER")?,
+            password,
+            system_name: required_var("RITHMIC_SYSTEM_NAME")?,
+            app_name: env::var("RITHMIC_APP_NAME").unwrap_or(defaults.app_name),
+            app_version: env::var("RITHMIC_APP_VERSION").unwrap_or(defaults.app_version),
+            aggregated_quotes,
+            proxy,
+            ..defaults
+        })
+    }
+
+    /// Reads a single connection from a TOML or JSON file, see `RithmicProfile`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<RithmicConnectionInfo, String> {
+        RithmicProfile::from_file(path)?.connection_info()
+    }
+}
+
+impl RithmicClientConfig {
+    /// Same as `RithmicConnectionInfo::from_env` with the gateway taken from `RITHMIC_GATEWAY`,
+    /// looked up through `RITHMIC_BOOTSTRAP_URL` when set, the account from `RITHMIC_ACCOUNT_ID`
+    /// and the instrument cache from `RITHMIC_INSTRUMENT_CACHE_FILE`. `RITHMIC_AUTO_GATEWAY=true`
+    /// picks the fastest gateway.
+    pub fn from_env() -> Result<RithmicClientConfig, String> {
+        let connection = RithmicConnectionInfo::from_env()?;
+
+        Ok(RithmicClientConfig {
+            connection,
+            gateway_name: env::var("RITHMIC_GATEWAY").ok(),
+            bootstrap_url: env::var("RITHMIC_BOOTSTRAP_URL").ok(),
+            auto_gateway: env::var("RITHMIC_AUTO_GATEWAY").is_ok_and(|v| v == "true"),
+            plants: RithmicPlantType::all(),
+            account_id: env::var("RITHMIC_ACCOUNT_ID").ok(),
+            instrument_cache_file: env::var_os("RITHMIC_INSTRUMENT_CACHE_FILE")
+                .map(PathBuf::from),
+        })
+    }
+}
+
+impl RithmicProfile {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<RithmicProfile, String> {
+        dotenv::dotenv().ok();
+
+        read_config(path.as_ref())
+    }
+
+    /// Connection info with the password resolved, a password written in the profile itself is
+    /// refused
+    pub fn connection_info(&self) -> Result<RithmicConnectionInfo, String> {
+        let mut connection = self.connection.clone();
+
+        if let Some(var) = &self.password_env {
+            connection.password = required_var(var)?;
+        } else if let Some(path) = &self.password_file {
+            connection.password = read_password_file(path)?;
+        } else if !connection.password.is_empty() {
+            return Err(
+                "plaintext password in profile, use password_env or password_file".to_string(),
+            );
+        }
+
+        if connection.password.is_empty() {
+            return Err("no password, set password_env or password_file".to_string());
+        }
+
+        Ok(connection)
+    }
+
+    pub fn client_config(&self) -> Result<RithmicClientConfig, String> {
+        Ok(RithmicClientConfig {
+            connection: self.connection_info()?,
+            gateway_name: self.gateway_name.clone(),
+            bootstrap_url: self.bootstrap_url.clone(),
+            auto_gateway: self.auto_gateway,
+            plants: self.plants.clone(),
+            account_id: self.account_id.clone(),
+            instrument_cache_file: self.instrument_cache_file.clone(),
+        })
+    }
+}
+
+impl RithmicProfiles {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<RithmicProfiles, String> {
+        dotenv::dotenv().ok();
+
+        read_config(path.as_ref())
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&RithmicProfile, String> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| format!("unknown profile {}", name))
+    }
+
+    /// Profile named by `RITHMIC_PROFILE`, then `default_profile`, then the only profile
+    pub fn default_profile(&self) -> Result<&RithmicProfile, String> {
+        let name = env::var("RITHMIC_PROFILE")
+            .ok()
+            .or_else(|| self.default_profile.clone());
+
+        match name {
+            Some(name) => self.profile(&name),
+            None if self.profiles.len() == 1 => Ok(self.profiles.values().next().unwrap()),
+            None => Err("no default profile, set default_profile or RITHMIC_PROFILE".to_string()),
+        }
+    }
+}
+
+fn required_var(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|_| format!("{} is not set", name))
+}
+
+fn read_password_file(path: &str) -> Result<String, String> {
+    fs::read_to_string(path)
+        .map(|password| password.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|e| format!("failed to read password file {}: {}", path, e))
+}
+
+/// Parses JSON when the extension is `.json`, TOML otherwise
+fn read_config<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&content).map_err(|e| format!("invalid {}: {}", path.display(), e))
+    } else {
+        toml::from_str(&content).map_err(|e| format!("invalid {}: {}", path.display(), e))
+    }
+}
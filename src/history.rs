@@ -0,0 +1,352 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::rti::{
+    ResponseTickBarReplay, ResponseTimeBarReplay, TickBar, TimeBar, messages::RithmicMessage,
+    request_tick_bar_replay, request_time_bar_replay,
+};
+
+/// Kind and size of the bars of a history query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BarSpec {
+    Second(i32),
+    Minute(i32),
+    Daily(i32),
+    Weekly(i32),
+    /// Number of trades per bar
+    Tick(i32),
+    /// Range of a bar, in ticks
+    Range(i32),
+    /// Volume per bar
+    Volume(i32),
+}
+
+impl BarSpec {
+    /// Time bar type and period, None for the tick based bars
+    pub fn time_bar(&self) -> Option<(request_time_bar_replay::BarType, i32)> {
+        match *self {
+            BarSpec::Second(period) => Some((request_time_bar_replay::BarType::SecondBar, period)),
+            BarSpec::Minute(period) => Some((request_time_bar_replay::BarType::MinuteBar, period)),
+            BarSpec::Daily(period) => Some((request_time_bar_replay::BarType::DailyBar, period)),
+            BarSpec::Weekly(period) => Some((request_time_bar_replay::BarType::WeeklyBar, period)),
+            _ => None,
+        }
+    }
+
+    /// Tick bar type and specifier, None for the time bars
+    pub fn tick_bar(&self) -> Option<(request_tick_bar_replay::BarType, String)> {
+        match *self {
+            BarSpec::Tick(size) => Some((request_tick_bar_replay::BarType::TickBar, size.to_string())),
+            BarSpec::Range(size) => Some((request_tick_bar_replay::BarType::RangeBar, size.to_string())),
+            BarSpec::Volume(size) => {
+                Some((request_tick_bar_replay::BarType::VolumeBar, size.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryDirection {
+    /// Oldest bar first
+    #[default]
+    Forwards,
+    /// Latest bar first
+    Backwards,
+}
+
+/// Bars of a symbol between two datetimes.
+///
+/// ```ignore
+/// let query = HistoryQuery::new("ESM5", "CME", BarSpec::Minute(5), start, end).backwards();
+/// let bars = history_plant.history(&query).await?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryQuery {
+    pub symbol: String,
+    pub exchange: String,
+    pub bar: BarSpec,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub direction: HistoryDirection,
+}
+
+impl HistoryQuery {
+    pub fn new<Tz: TimeZone>(
+        symbol: &str,
+        exchange: &str,
+        bar: BarSpec,
+        start: DateTime<Tz>,
+        end: DateTime<Tz>,
+    ) -> HistoryQuery {
+        HistoryQuery {
+            symbol: symbol.to_string(),
+            exchange: exchange.to_string(),
+            bar,
+            start: start.with_timezone(&Utc),
+            end: end.with_timezone(&Utc),
+            direction: HistoryDirection::Forwards,
+        }
+    }
+
+    pub fn with_direction(mut self, direction: HistoryDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn backwards(self) -> Self {
+        self.with_direction(HistoryDirection::Backwards)
+    }
+
+    /// `start` and `end` as the epoch seconds Rithmic expects, an error when `start` is after
+    /// `end`
+    pub fn index_range(&self) -> Result<(i32, i32), String> {
+        if self.start > self.end {
+            return Err(format!("history: start {} is after end {}", self.start, self.end));
+        }
+
+        let to_index = |datetime: &DateTime<Utc>| {
+            i32::try_from(datetime.timestamp())
+                .map_err(|_| format!("history: {} is out of range", datetime))
+        };
+
+        Ok((to_index(&self.start)?, to_index(&self.end)?))
+    }
+
+    /// Bar of a live `TimeBar` or `TickBar` update of this query, None for any other update
+    pub fn live_bar(&self, message: RithmicMessage) -> Option<Bar> {
+        let bar = match message {
+            RithmicMessage::TimeBar(bar) => {
+                let (bar_type, period) = self.bar.time_bar()?;
+
+                if bar.r#type != Some(bar_type.into())
+                    || bar.period.as_deref() != Some(period.to_string().as_str())
+                {
+                    return None;
+                }
+
+                Bar::from(bar)
+            }
+            RithmicMessage::TickBar(bar) => {
+                let (bar_type, specifier) = self.bar.tick_bar()?;
+
+                if bar.r#type != Some(bar_type.into())
+                    || bar.type_specifier.as_deref() != Some(specifier.as_str())
+                {
+                    return None;
+                }
+
+                Bar::from(bar)
+            }
+            _ => return None,
+        };
+
+        (bar.symbol == self.symbol && bar.exchange == self.exchange).then_some(bar)
+    }
+}
+
+/// OHLCV bar of a time or tick bar replay
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bar {
+    pub symbol: String,
+    pub exchange: String,
+    /// Time of the bar, the first trade for the tick based bars
+    pub timestamp: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    pub bid_volume: u64,
+    pub ask_volume: u64,
+    pub num_trades: u64,
+}
+
+impl From<ResponseTimeBarReplay> for Bar {
+    fn from(bar: ResponseTimeBarReplay) -> Bar {
+        Bar {
+            symbol: bar.symbol.unwrap_or_default(),
+            exchange: bar.exchange.unwrap_or_default(),
+            timestamp: to_datetime(bar.marker.unwrap_or_default(), 0),
+            open: bar.open_price.unwrap_or_default(),
+            high: bar.high_price.unwrap_or_default(),
+            low: bar.low_price.unwrap_or_default(),
+            close: bar.close_price.unwrap_or_default(),
+            volume: bar.volume.unwrap_or_default(),
+            bid_volume: bar.bid_volume.unwrap_or_default(),
+            ask_volume: bar.ask_volume.unwrap_or_default(),
+            num_trades: bar.num_trades.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<ResponseTickBarReplay> for Bar {
+    fn from(bar: ResponseTickBarReplay) -> Bar {
+        let ssboe = bar.data_bar_ssboe.first().copied().unwrap_or_default();
+        let usecs = bar.data_bar_usecs.first().copied().unwrap_or_default();
+
+        Bar {
+            symbol: bar.symbol.unwrap_or_default(),
+            exchange: bar.exchange.unwrap_or_default(),
+            timestamp: to_datetime(ssboe, usecs),
+            open: bar.open_price.unwrap_or_default(),
+            high: bar.high_price.unwrap_or_default(),
+            low: bar.low_price.unwrap_or_default(),
+            close: bar.close_price.unwrap_or_default(),
+            volume: bar.volume.unwrap_or_default(),
+            bid_volume: bar.bid_volume.unwrap_or_default(),
+            ask_volume: bar.ask_volume.unwrap_or_default(),
+            num_trades: bar.num_trades.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<TimeBar> for Bar {
+    fn from(bar: TimeBar) -> Bar {
+        Bar {
+            symbol: bar.symbol.unwrap_or_default(),
+            exchange: bar.exchange.unwrap_or_default(),
+            timestamp: to_datetime(bar.marker.unwrap_or_default(), 0),
+            open: bar.open_price.unwrap_or_default(),
+            high: bar.high_price.unwrap_or_default(),
+            low: bar.low_price.unwrap_or_default(),
+            close: bar.close_price.unwrap_or_default(),
+            volume: bar.volume.unwrap_or_default(),
+            bid_volume: bar.bid_volume.unwrap_or_default(),
+            ask_volume: bar.ask_volume.unwrap_or_default(),
+            num_trades: bar.num_trades.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<TickBar> for Bar {
+    fn from(bar: TickBar) -> Bar {
+        let ssboe = bar.data_bar_ssboe.first().copied().unwrap_or_default();
+        let usecs = bar.data_bar_usecs.first().copied().unwrap_or_default();
+
+        Bar {
+            symbol: bar.symbol.unwrap_or_default(),
+            exchange: bar.exchange.unwrap_or_default(),
+            timestamp: to_datetime(ssboe, usecs),
+            open: bar.open_price.unwrap_or_default(),
+            high: bar.high_price.unwrap_or_default(),
+            low: bar.low_price.unwrap_or_default(),
+            close: bar.close_price.unwrap_or_default(),
+            volume: bar.volume.unwrap_or_default(),
+            bid_volume: bar.bid_volume.unwrap_or_default(),
+            ask_volume: bar.ask_volume.unwrap_or_default(),
+            num_trades: bar.num_trades.unwrap_or_default(),
+        }
+    }
+}
+
+/// Merges a replay with the live bars, see `RithmicHistoryPlantHandle::backfill_and_subscribe`
+#[derive(Debug, Default)]
+pub struct BarStitcher {
+    /// Last replayed bar, possibly still forming
+    held: Option<Bar>,
+    last: Option<DateTime<Utc>>,
+}
+
+impl BarStitcher {
+    /// Holds a replayed bar back, returning the previous one
+    pub fn push_replayed(&mut self, bar: Bar) -> Option<Bar> {
+        self.held.replace(bar)
+    }
+
+    /// Bars to emit for a live bar, the replayed bar it completes or supersedes included
+    pub fn push_live(&mut self, bar: Bar) -> Vec<Bar> {
+        if self.last.is_some_and(|last| bar.timestamp <= last) {
+            return vec![];
+        }
+
+        let mut bars = vec![];
+
+        if let Some(held) = self.held.take() {
+            if bar.timestamp < held.timestamp {
+                self.held = Some(held);
+
+                return bars;
+            }
+
+            if bar.timestamp > held.timestamp {
+                bars.push(held);
+            }
+        }
+
+        self.last = Some(bar.timestamp);
+        bars.push(bar);
+
+        bars
+    }
+}
+
+fn to_datetime(ssboe: i32, usecs: i32) -> DateTime<Utc> {
+    DateTime::from_timestamp(ssboe.into(), (usecs.max(0) as u32).saturating_mul(1000))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(minute: u32, close: f64) -> Bar {
+        Bar {
+            symbol: "ESM5".to_string(),
+            exchange: "CME".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 2, 14, minute, 0).unwrap(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1,
+            bid_volume: 0,
+            ask_volume: 0,
+            num_trades: 1,
+        }
+    }
+
+    fn closes(bars: &[Bar]) -> Vec<f64> {
+        bars.iter().map(|bar| bar.close).collect()
+    }
+
+    #[test]
+    fn replayed_bars_are_held_back_by_one() {
+        let mut stitcher = BarStitcher::default();
+
+        assert_eq!(stitcher.push_replayed(bar(0, 1.0)), None);
+        assert_eq!(stitcher.push_replayed(bar(1, 2.0)), Some(bar(0, 1.0)));
+    }
+
+    #[test]
+    fn live_bars_covered_by_the_replay_are_dropped() {
+        let mut stitcher = BarStitcher::default();
+
+        stitcher.push_replayed(bar(0, 1.0));
+        stitcher.push_replayed(bar(1, 2.0));
+
+        assert!(stitcher.push_live(bar(0, 10.0)).is_empty());
+        assert_eq!(closes(&stitcher.push_live(bar(2, 3.0))), vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn live_bar_supersedes_the_forming_replayed_bar() {
+        let mut stitcher = BarStitcher::default();
+
+        stitcher.push_replayed(bar(0, 1.0));
+
+        assert_eq!(closes(&stitcher.push_live(bar(0, 1.5))), vec![1.5]);
+        assert_eq!(closes(&stitcher.push_live(bar(1, 2.0))), vec![2.0]);
+    }
+
+    #[test]
+    fn live_bars_are_emitted_in_order_once() {
+        let mut stitcher = BarStitcher::default();
+
+        assert_eq!(closes(&stitcher.push_live(bar(0, 1.0))), vec![1.0]);
+        assert_eq!(closes(&stitcher.push_live(bar(1, 2.0))), vec![2.0]);
+        assert!(stitcher.push_live(bar(1, 2.5)).is_empty());
+        assert!(stitcher.push_live(bar(0, 3.0)).is_empty());
+        assert_eq!(closes(&stitcher.push_live(bar(2, 4.0))), vec![4.0]);
+    }
+}
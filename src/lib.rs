@@ -1,5 +1,13 @@
+pub mod aggregator;
 pub mod api;
+pub mod client;
+pub mod config;
+pub mod history;
 pub mod plants;
+pub mod proxy;
 pub mod request_handler;
+pub mod risk;
 pub mod rti;
+pub mod throttle;
+pub mod tls;
 pub mod ws;
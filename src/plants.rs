@@ -1,5 +1,7 @@
+pub mod plant;
 pub mod shared_plant;
 pub mod order_plant;
 pub mod pnl_plant;
 pub mod ticker_plant;
-pub mod history_plant;
\ No newline at end of file
+pub mod history_plant;
+pub mod repository_plant;
\ No newline at end of file
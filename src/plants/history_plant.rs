@@ -1,41 +1,23 @@
+use std::collections::{HashSet, VecDeque};
+
 use async_trait::async_trait;
-use tracing::{event, Level};
 
 use crate::{
-    api::{
-        RithmicConnectionInfo,
-        receiver_api::{RithmicReceiverApi, RithmicResponse},
-        sender_api::RithmicSenderApi,
-    },
-    request_handler::{RithmicRequest, RithmicRequestHandler},
+    api::receiver_api::RithmicResponse,
+    history::{Bar, BarStitcher, HistoryDirection, HistoryQuery},
+    plants::plant::{PlantActorCore, PlantExtension, RithmicPlant, RithmicPlantHandle},
     rti::{
         *,
+        messages::RithmicMessage,
         request_login::SysInfraType,
     },
-    ws::{get_heartbeat_interval, PlantActor, RithmicStream, connect},
-};
-
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt,
-};
-
-use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{Error, Message},
-    WebSocketStream,
-    MaybeTlsStream
 };
 
-use tokio::{
-    net::TcpStream,
-    sync::{broadcast::Sender, oneshot},
-    time::Interval,
-};
-use crate::plants::ticker_plant::TickerPlantCommand;
+use chrono::Utc;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tracing::{event, Level};
 
 pub enum HistoryPlantCommand {
-    Close,
     GetHistoricalTickBar {
         symbol: String,
         exchange: String,
@@ -59,14 +41,6 @@ pub enum HistoryPlantCommand {
         time_order: request_time_bar_replay::TimeOrder,
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
-    Login {
-        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
-    },
-    Logout {
-        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
-    },
-    SendHeartbeat {},
-    SetLogin,
     SubscribeTickBar {
         symbol: String,
         exchange: String,
@@ -84,178 +58,36 @@ pub enum HistoryPlantCommand {
         request_type: request_time_bar_update::Request,
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
+    GetVolumeProfileMinuteBars {
+        symbol: String,
+        exchange: String,
+        bar_type_period: i32,
+        start_index: i32,
+        finish_index: i32,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ResumeBars {
+        request_key: String,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
 }
 
-pub struct RithmicHistoryPlant {
-    pub connection_handle: tokio::task::JoinHandle<()>,
-    sender: tokio::sync::mpsc::Sender<HistoryPlantCommand>,
-    subscription_sender: Sender<RithmicResponse>,
-}
-
-impl RithmicHistoryPlant {
-    pub async fn new(conn_info: &RithmicConnectionInfo) -> RithmicHistoryPlant {
-        let (req_tx, req_rx) = tokio::sync::mpsc::channel::<HistoryPlantCommand>(32);
-        let (sub_tx, _sub_rx) = tokio::sync::broadcast::channel(1024);
-
-        let mut history_plant = HistoryPlant::new(req_rx, sub_tx.clone(), conn_info)
-            .await
-            .unwrap();
-
-        let connection_handle = tokio::spawn(async move {
-            history_plant.run().await;
-        });
-
-        RithmicHistoryPlant {
-            connection_handle,
-            sender: req_tx,
-            subscription_sender: sub_tx,
-        }
-    }
-}
-
-impl RithmicStream for RithmicHistoryPlant {
-    type Handle = RithmicHistoryPlantHandle;
-
-    fn get_handle(&self) -> RithmicHistoryPlantHandle {
-        RithmicHistoryPlantHandle {
-            sender: self.sender.clone(),
-            subscription_sender: self.subscription_sender.clone(),
-            subscription_receiver: self.subscription_sender.subscribe(),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct HistoryPlant {
-    config: RithmicConnectionInfo,
-    interval: Interval,
-    logged_in: bool,
-    request_handler: RithmicRequestHandler,
-    request_receiver: tokio::sync::mpsc::Receiver<HistoryPlantCommand>,
-    rithmic_reader: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    rithmic_receiver_api: RithmicReceiverApi,
-    rithmic_sender: SplitSink<
-        WebSocketStream<MaybeTlsStream<TcpStream>>,
-        Message,
-    >,
-
-    rithmic_sender_api: RithmicSenderApi,
-    subscription_sender: Sender<RithmicResponse>,
-}
+pub type RithmicHistoryPlant = RithmicPlant<HistoryPlant>;
 
-impl HistoryPlant {
-    async fn new(
-        request_receiver: tokio::sync::mpsc::Receiver<HistoryPlantCommand>,
-        subscription_sender: Sender<RithmicResponse>,
-        conn_info: &RithmicConnectionInfo,
-    ) -> Result<HistoryPlant, ()> {
-        let config = conn_info.clone();
-
-        let ws_stream = connect(&config.url).await.unwrap();
-        let (rithmic_sender, rithmic_reader) = ws_stream.split();
-        let rithmic_sender_api = RithmicSenderApi::new(&config);
-        let rithmic_receiver_api = RithmicReceiverApi {
-            source: "history_plant".to_string(),
-        };
+pub type RithmicHistoryPlantHandle = RithmicPlantHandle<HistoryPlantCommand>;
 
-        let interval = get_heartbeat_interval();
-
-        Ok(HistoryPlant {
-            config,
-            interval,
-            logged_in: false,
-            request_handler: RithmicRequestHandler::new(),
-            request_receiver,
-            rithmic_reader,
-            rithmic_receiver_api,
-            rithmic_sender_api,
-            rithmic_sender,
-            subscription_sender,
-        })
-    }
-}
+#[derive(Debug, Default)]
+pub struct HistoryPlant;
 
 #[async_trait]
-impl PlantActor for HistoryPlant {
+impl PlantExtension for HistoryPlant {
     type Command = HistoryPlantCommand;
 
-    /// Execute the history plant in its own thread
-    /// We will listen for messages from request_receiver and forward them to Rithmic
-    /// while also listening for messages from Rithmic and forwarding them to subscription_sender
-    /// or request handler
-    async fn run(&mut self) {
-        loop {
-            tokio::select! {
-                _ = self.interval.tick() => {
-                    if self.logged_in {
-                        self.handle_command(HistoryPlantCommand::SendHeartbeat {}).await;
-                    }
-                }
-                Some(message) = self.request_receiver.recv() => {
-                    self.handle_command(message).await;
-                }
-                Some(message) = self.rithmic_reader.next() => {
-                    let stop = self.handle_rithmic_message(message).await.unwrap();
-
-                    if stop {
-                        break;
-                    }
-                }
-                else => { break }
-            }
-        }
-    }
-
-    async fn handle_rithmic_message(
-        &mut self,
-        message: Result<Message, Error>,
-    ) -> Result<bool, ()> {
-        let mut stop = false;
-
-        match message {
-            Ok(Message::Close(frame)) => {
-                event!(
-                    Level::INFO,
-                    "history_plant received close frame: {:?}",
-                    frame
-                );
-
-                stop = true;
-            }
-            Ok(Message::Binary(data)) => {
-                let response = self.rithmic_receiver_api.buf_to_message(data).unwrap();
-
-                if response.is_update {
-                    self.subscription_sender.send(response).unwrap();
-                } else {
-                    self.request_handler.handle_response(response);
-                }
-            }
-            Err(Error::ConnectionClosed) => {
-                event!(Level::INFO, "history_plant connection closed");
-
-                stop = true;
-            }
-            _ => {
-                event!(
-                    Level::WARN,
-                    "history_plant received unknown message {:?}",
-                    message
-                );
-            }
-        }
-
-        Ok(stop)
-    }
+    const NAME: &'static str = "history_plant";
+    const INFRA_TYPE: SysInfraType = SysInfraType::HistoryPlant;
 
-    async fn handle_command(&mut self, command: HistoryPlantCommand) {
+    async fn handle_command(&mut self, core: &mut PlantActorCore, command: HistoryPlantCommand) {
         match command {
-            HistoryPlantCommand::Close => {
-                self.rithmic_sender
-                    .send(Message::Close(None))
-                    .await
-                    .unwrap();
-            }
             HistoryPlantCommand::GetHistoricalTickBar {
                 symbol,
                 exchange,
@@ -268,7 +100,7 @@ impl PlantActor for HistoryPlant {
                 time_order,
                 response_sender,
             } => {
-                let (sub_buf, id) = self.rithmic_sender_api.request_tick_bar_replay(
+                let request = core.rithmic_sender_api.request_tick_bar_replay(
                     &symbol,
                     &exchange,
                     bar_type,
@@ -280,15 +112,7 @@ impl PlantActor for HistoryPlant {
                     time_order,
                 );
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(sub_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
             HistoryPlantCommand::GetHistoricalTimeBar {
                 symbol,
@@ -301,7 +125,7 @@ impl PlantActor for HistoryPlant {
                 time_order,
                 response_sender,
             } => {
-                let (sub_buf, id) = self.rithmic_sender_api.request_time_bar_replay(
+                let request = core.rithmic_sender_api.request_time_bar_replay(
                     &symbol,
                     &exchange,
                     bar_type,
@@ -312,59 +136,7 @@ impl PlantActor for HistoryPlant {
                     time_order,
                 );
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(sub_buf))
-                    .await
-                    .unwrap();
-            }
-            HistoryPlantCommand::Login { response_sender } => {
-                let (login_buf, id) = self.rithmic_sender_api.request_login(
-                    &self.config.system_name,
-                    SysInfraType::HistoryPlant,
-                    &self.config.user,
-                    &self.config.password,
-                );
-
-                event!(Level::INFO, "history_plant: sending login request {}", id);
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(login_buf))
-                    .await
-                    .unwrap();
-            }
-            HistoryPlantCommand::Logout { response_sender } => {
-                let (logout_buf, id) = self.rithmic_sender_api.request_logout();
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(logout_buf))
-                    .await
-                    .unwrap();
-            }
-            HistoryPlantCommand::SendHeartbeat {} => {
-                let (heartbeat_buf, _id) = self.rithmic_sender_api.request_heartbeat();
-
-                let _ = self
-                    .rithmic_sender
-                    .send(Message::Binary(heartbeat_buf))
-                    .await;
-            }
-            HistoryPlantCommand::SetLogin => {
-                self.logged_in = true;
+                core.send_request(request, response_sender, false).await;
             }
             HistoryPlantCommand::SubscribeTickBar {
                 symbol,
@@ -375,7 +147,7 @@ impl PlantActor for HistoryPlant {
                 request_type,
                 response_sender,
             } => {
-                let (sub_buf, id) = self.rithmic_sender_api.request_tick_bar_update(
+                let request = core.rithmic_sender_api.request_tick_bar_update(
                     &symbol,
                     &exchange,
                     bar_type,
@@ -384,15 +156,7 @@ impl PlantActor for HistoryPlant {
                     request_type,
                 );
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(sub_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
             HistoryPlantCommand::SubscribeTimeBar {
                 symbol,
@@ -402,7 +166,7 @@ impl PlantActor for HistoryPlant {
                 request_type,
                 response_sender,
             } => {
-                let (sub_buf, id) = self.rithmic_sender_api.request_time_bar_update(
+                let request = core.rithmic_sender_api.request_time_bar_update(
                     &symbol,
                     &exchange,
                     bar_type,
@@ -410,74 +174,43 @@ impl PlantActor for HistoryPlant {
                     request_type,
                 );
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(sub_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
-        }
-    }
-}
-
-pub struct RithmicHistoryPlantHandle {
-    sender: tokio::sync::mpsc::Sender<HistoryPlantCommand>,
-    // Used for cloning
-    subscription_sender: tokio::sync::broadcast::Sender<RithmicResponse>,
-    pub subscription_receiver: tokio::sync::broadcast::Receiver<RithmicResponse>,
-}
-
-impl RithmicHistoryPlantHandle {
-    pub async fn login(&self) -> Result<RithmicResponse, String> {
-        event!(Level::INFO, "history_plant: logging in");
-
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = HistoryPlantCommand::Login {
-            response_sender: tx,
-        };
-
-        let _ = self.sender.send(command).await;
-        let response = rx.await.unwrap()?.remove(0);
-
-        if response.error.is_none() {
-            let _ = self.sender.send(HistoryPlantCommand::SetLogin).await;
-
-            event!(Level::INFO, "history_plant: logged in");
+            HistoryPlantCommand::GetVolumeProfileMinuteBars {
+                symbol,
+                exchange,
+                bar_type_period,
+                start_index,
+                finish_index,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_volume_profile_minute_bars(
+                    &symbol,
+                    &exchange,
+                    bar_type_period,
+                    start_index,
+                    finish_index,
+                    None,
+                );
 
-            Ok(response)
-        } else {
-            event!(
-                Level::ERROR,
-                "history_plant: login failed {:?}",
-                response.error
-            );
+                core.send_request(request, response_sender, false).await;
+            }
+            HistoryPlantCommand::ResumeBars {
+                request_key,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_resume_bars(&request_key);
 
-            Err(response.error.unwrap())
+                core.send_request(request, response_sender, false).await;
+            }
         }
     }
+}
 
-    pub async fn disconnect(&self) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = HistoryPlantCommand::Logout {
-            response_sender: tx,
-        };
-
-        let _ = self.sender.send(command).await;
-        let mut r = rx.await.unwrap()?;
-        let _ = self.sender.send(HistoryPlantCommand::Close).await;
-        let response = r.remove(0);
-
-        self.subscription_sender.send(response.clone()).unwrap();
-
-        Ok(response)
-    }
-
+impl RithmicPlantHandle<HistoryPlantCommand> {
+    /// Tick bars between `start_index` and `finish_index` (unix seconds), see
+    /// `stream_historical_tick_bar`
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_historical_tick_bar(
         &self,
         symbol: String,
@@ -489,10 +222,8 @@ impl RithmicHistoryPlantHandle {
         finish_index: i32,
         direction: request_tick_bar_replay::Direction,
         time_order: request_tick_bar_replay::TimeOrder,
-    ) -> Result<Vec<RithmicResponse>, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = HistoryPlantCommand::GetHistoricalTickBar {
+    ) -> Result<Vec<ResponseTickBarReplay>, String> {
+        let bars = self.stream_historical_tick_bar(
             symbol,
             exchange,
             bar_type,
@@ -502,14 +233,55 @@ impl RithmicHistoryPlantHandle {
             finish_index,
             direction,
             time_order,
-            response_sender: tx,
-        };
+        );
 
-        let _ = self.sender.send(command).await;
+        collect_replay(bars).await
+    }
 
-        Ok(rx.await.unwrap()?)
+    /// Streams the tick bars of a replay as they arrive.
+    ///
+    /// The range is split in windows of `TICK_BAR_WINDOW_SECONDS`, each window follows the resume
+    /// keys of Rithmic until it is complete and the bars repeated on the edge of two windows are
+    /// dropped. The channel closes after the last bar or after the first error.
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_historical_tick_bar(
+        &self,
+        symbol: String,
+        exchange: String,
+        bar_type: request_tick_bar_replay::BarType,
+        bar_sub_type: request_tick_bar_replay::BarSubType,
+        bar_type_specifier: String,
+        start_index: i32,
+        finish_index: i32,
+        direction: request_tick_bar_replay::Direction,
+        time_order: request_tick_bar_replay::TimeOrder,
+    ) -> mpsc::Receiver<Result<ResponseTickBarReplay, String>> {
+        let windows = replay_windows(
+            start_index,
+            finish_index,
+            TICK_BAR_WINDOW_SECONDS,
+            time_order == request_tick_bar_replay::TimeOrder::Forwards,
+        );
+
+        self.stream_replay(windows, move |start_index, finish_index, response_sender| {
+            HistoryPlantCommand::GetHistoricalTickBar {
+                symbol: symbol.clone(),
+                exchange: exchange.clone(),
+                bar_type,
+                bar_sub_type,
+                bar_type_specifier: bar_type_specifier.clone(),
+                start_index,
+                finish_index,
+                direction,
+                time_order,
+                response_sender,
+            }
+        })
     }
 
+    /// Time bars between `start_index` and `finish_index` (unix seconds), see
+    /// `stream_historical_time_bar`
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_historical_time_bar(
         &self,
         symbol: String,
@@ -520,10 +292,8 @@ impl RithmicHistoryPlantHandle {
         finish_index: i32,
         direction: request_time_bar_replay::Direction,
         time_order: request_time_bar_replay::TimeOrder,
-    ) -> Result<Vec<RithmicResponse>, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = HistoryPlantCommand::GetHistoricalTimeBar {
+    ) -> Result<Vec<ResponseTimeBarReplay>, String> {
+        let bars = self.stream_historical_time_bar(
             symbol,
             exchange,
             bar_type,
@@ -532,12 +302,45 @@ impl RithmicHistoryPlantHandle {
             finish_index,
             direction,
             time_order,
-            response_sender: tx,
-        };
+        );
 
-        let _ = self.sender.send(command).await;
+        collect_replay(bars).await
+    }
+
+    /// Streams the time bars of a replay as they arrive, windowed and resumed like
+    /// `stream_historical_tick_bar`
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_historical_time_bar(
+        &self,
+        symbol: String,
+        exchange: String,
+        bar_type: request_time_bar_replay::BarType,
+        bar_type_period: i32,
+        start_index: i32,
+        finish_index: i32,
+        direction: request_time_bar_replay::Direction,
+        time_order: request_time_bar_replay::TimeOrder,
+    ) -> mpsc::Receiver<Result<ResponseTimeBarReplay, String>> {
+        let windows = replay_windows(
+            start_index,
+            finish_index,
+            time_bar_window_seconds(bar_type, bar_type_period),
+            time_order == request_time_bar_replay::TimeOrder::Forwards,
+        );
 
-        Ok(rx.await.unwrap()?)
+        self.stream_replay(windows, move |start_index, finish_index, response_sender| {
+            HistoryPlantCommand::GetHistoricalTimeBar {
+                symbol: symbol.clone(),
+                exchange: exchange.clone(),
+                bar_type,
+                bar_type_period,
+                start_index,
+                finish_index,
+                direction,
+                time_order,
+                response_sender,
+            }
+        })
     }
 
     pub async fn subscribe_tick_bar(
@@ -548,53 +351,712 @@ impl RithmicHistoryPlantHandle {
         bar_sub_type: request_tick_bar_update::BarSubType,
         bar_type_specifier: &str,
     ) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+        let mut r = self
+            .request(|response_sender| HistoryPlantCommand::SubscribeTickBar {
+                symbol: symbol.to_string(),
+                exchange: exchange.to_string(),
+                bar_type,
+                bar_sub_type,
+                bar_type_specifier: bar_type_specifier.to_string(),
+                request_type: request_tick_bar_update::Request::Subscribe,
+                response_sender,
+            })
+            .await?;
 
-        let command = HistoryPlantCommand::SubscribeTickBar {
-            symbol: symbol.to_string(),
-            exchange: exchange.to_string(),
-            bar_type,
-            bar_sub_type,
-            bar_type_specifier: bar_type_specifier.to_string(),
-            request_type: request_tick_bar_update::Request::Subscribe,
-            response_sender: tx,
+        Ok(r.remove(0))
+    }
+
+    pub async fn subscribe_time_bar(
+        &self,
+        symbol: &str,
+        exchange: &str,
+        bar_type: request_time_bar_update::BarType,
+        bar_type_period: i32
+    ) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| HistoryPlantCommand::SubscribeTimeBar {
+                symbol: symbol.to_string(),
+                exchange: exchange.to_string(),
+                bar_type,
+                bar_type_period,
+                request_type: request_time_bar_update::Request::Subscribe,
+                response_sender,
+            })
+            .await?;
+
+        Ok(r.remove(0))
+    }
+
+    /// Typed bars of `query`, see `stream_history`
+    pub async fn history(&self, query: &HistoryQuery) -> Result<Vec<Bar>, String> {
+        collect_replay(self.stream_history(query)?).await
+    }
+
+    /// Streams the typed bars of `query`, replayed as time or tick bars depending on its
+    /// `BarSpec`
+    pub fn stream_history(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<mpsc::Receiver<Result<Bar, String>>, String> {
+        let (start_index, finish_index) = query.index_range()?;
+        let symbol = query.symbol.clone();
+        let exchange = query.exchange.clone();
+
+        if let Some((bar_type, bar_type_period)) = query.bar.time_bar() {
+            let (direction, time_order) = match query.direction {
+                HistoryDirection::Forwards => (
+                    request_time_bar_replay::Direction::First,
+                    request_time_bar_replay::TimeOrder::Forwards,
+                ),
+                HistoryDirection::Backwards => (
+                    request_time_bar_replay::Direction::Last,
+                    request_time_bar_replay::TimeOrder::Backwards,
+                ),
+            };
+
+            let bars = self.stream_historical_time_bar(
+                symbol,
+                exchange,
+                bar_type,
+                bar_type_period,
+                start_index,
+                finish_index,
+                direction,
+                time_order,
+            );
+
+            return Ok(map_replay(bars));
+        }
+
+        let Some((bar_type, bar_type_specifier)) = query.bar.tick_bar() else {
+            return Err(format!("history_plant: unsupported bar {:?}", query.bar));
         };
 
-        let _ = self.sender.send(command).await;
+        let (direction, time_order) = match query.direction {
+            HistoryDirection::Forwards => (
+                request_tick_bar_replay::Direction::First,
+                request_tick_bar_replay::TimeOrder::Forwards,
+            ),
+            HistoryDirection::Backwards => (
+                request_tick_bar_replay::Direction::Last,
+                request_tick_bar_replay::TimeOrder::Backwards,
+            ),
+        };
+
+        let bars = self.stream_historical_tick_bar(
+            symbol,
+            exchange,
+            bar_type,
+            request_tick_bar_replay::BarSubType::Regular,
+            bar_type_specifier,
+            start_index,
+            finish_index,
+            direction,
+            time_order,
+        );
 
-        Ok(rx.await.unwrap()?.remove(0))
+        Ok(map_replay(bars))
     }
 
-    pub async fn subscribe_time_bar(
+    pub async fn unsubscribe_tick_bar(
+        &self,
+        symbol: &str,
+        exchange: &str,
+        bar_type: request_tick_bar_update::BarType,
+        bar_sub_type: request_tick_bar_update::BarSubType,
+        bar_type_specifier: &str,
+    ) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| HistoryPlantCommand::SubscribeTickBar {
+                symbol: symbol.to_string(),
+                exchange: exchange.to_string(),
+                bar_type,
+                bar_sub_type,
+                bar_type_specifier: bar_type_specifier.to_string(),
+                request_type: request_tick_bar_update::Request::Unsubscribe,
+                response_sender,
+            })
+            .await?;
+
+        Ok(r.remove(0))
+    }
+
+    pub async fn unsubscribe_time_bar(
         &self,
         symbol: &str,
         exchange: &str,
         bar_type: request_time_bar_update::BarType,
         bar_type_period: i32
     ) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+        let mut r = self
+            .request(|response_sender| HistoryPlantCommand::SubscribeTimeBar {
+                symbol: symbol.to_string(),
+                exchange: exchange.to_string(),
+                bar_type,
+                bar_type_period,
+                request_type: request_time_bar_update::Request::Unsubscribe,
+                response_sender,
+            })
+            .await?;
 
-        let command = HistoryPlantCommand::SubscribeTimeBar {
-            symbol: symbol.to_string(),
-            exchange: exchange.to_string(),
-            bar_type,
-            bar_type_period,
-            request_type: request_time_bar_update::Request::Subscribe,
-            response_sender: tx,
+        Ok(r.remove(0))
+    }
+
+    /// Replays `query` up to now then keeps streaming its live bars, as one ordered stream
+    /// without gaps or duplicates.
+    ///
+    /// The live bars are subscribed before the replay starts and buffered until it is over, the
+    /// ones the replay already covered are dropped. The last replayed bar may still be forming, it
+    /// is held back until the first live bar tells whether it is complete. The stream ends with an
+    /// `Err` when more than `MAX_BUFFERED_LIVE_BARS` arrive during the replay or live bars are
+    /// skipped, and without one when the plant closes. The live bars are unsubscribed once the
+    /// stream ends or its receiver is dropped.
+    pub async fn backfill_and_subscribe(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<mpsc::Receiver<Result<Bar, String>>, String> {
+        let mut live = self.resubscribe();
+
+        self.update_live_bars(query, true).await?;
+
+        let query = HistoryQuery {
+            end: query.end.max(Utc::now()),
+            direction: HistoryDirection::Forwards,
+            ..query.clone()
         };
 
-        let _ = self.sender.send(command).await;
+        let mut replay = match self.stream_history(&query) {
+            Ok(replay) => replay,
+            Err(e) => {
+                let _ = self.update_live_bars(&query, false).await;
+
+                return Err(e);
+            }
+        };
+
+        let (tx, rx) = mpsc::channel(REPLAY_CHANNEL_CAPACITY);
+        let handle = self.clone();
+
+        tokio::spawn(async move {
+            let stitched = async {
+                let mut stitcher = BarStitcher::default();
+                let mut buffered = VecDeque::new();
+
+                loop {
+                    tokio::select! {
+                        bar = replay.recv() => match bar {
+                            Some(Ok(bar)) => {
+                                if let Some(bar) = stitcher.push_replayed(bar)
+                                    && tx.send(Ok(bar)).await.is_err()
+                                {
+                                    return;
+                                }
+                            }
+                            Some(Err(e)) => {
+                                let _ = tx.send(Err(e)).await;
+
+                                return;
+                            }
+                            None => break,
+                        },
+                        response = live.recv() => match response {
+                            Ok(response) => {
+                                buffered.extend(query.live_bar(response.message));
+
+                                if buffered.len() > MAX_BUFFERED_LIVE_BARS {
+                                    let _ = tx
+                                        .send(Err(format!(
+                                            "history_plant: more than {} live bars arrived during the replay",
+                                            MAX_BUFFERED_LIVE_BARS
+                                        )))
+                                        .await;
+
+                                    return;
+                                }
+                            }
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                let _ = tx.send(Err(lagged_error(skipped))).await;
+
+                                return;
+                            }
+                            Err(broadcast::error::RecvError::Closed) => return,
+                        },
+                    }
+                }
+
+                for bar in buffered {
+                    for bar in stitcher.push_live(bar) {
+                        if tx.send(Ok(bar)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+
+                loop {
+                    let bar = match live.recv().await {
+                        Ok(response) => query.live_bar(response.message),
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            let _ = tx.send(Err(lagged_error(skipped))).await;
+
+                            return;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    };
+
+                    for bar in bar.into_iter().flat_map(|bar| stitcher.push_live(bar)) {
+                        if tx.send(Ok(bar)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            };
+
+            stitched.await;
+
+            if let Err(e) = handle.update_live_bars(&query, false).await {
+                event!(Level::WARN, "history_plant: unsubscribing the live bars failed: {}", e);
+            }
+        });
+
+        Ok(rx)
+    }
+
+    /// Subscribes to or unsubscribes from the live bars of `query`
+    async fn update_live_bars(&self, query: &HistoryQuery, subscribe: bool) -> Result<(), String> {
+        if let Some((bar_type, bar_type_period)) = query.bar.time_bar() {
+            let bar_type = request_time_bar_update::BarType::try_from(i32::from(bar_type))
+                .map_err(|e| e.to_string())?;
+
+            if subscribe {
+                self.subscribe_time_bar(&query.symbol, &query.exchange, bar_type, bar_type_period)
+                    .await?;
+            } else {
+                self.unsubscribe_time_bar(&query.symbol, &query.exchange, bar_type, bar_type_period)
+                    .await?;
+            }
+        } else if let Some((bar_type, bar_type_specifier)) = query.bar.tick_bar() {
+            let bar_type = request_tick_bar_update::BarType::try_from(i32::from(bar_type))
+                .map_err(|e| e.to_string())?;
+            let bar_sub_type = request_tick_bar_update::BarSubType::Regular;
+
+            if subscribe {
+                self.subscribe_tick_bar(
+                    &query.symbol,
+                    &query.exchange,
+                    bar_type,
+                    bar_sub_type,
+                    &bar_type_specifier,
+                )
+                .await?;
+            } else {
+                self.unsubscribe_tick_bar(
+                    &query.symbol,
+                    &query.exchange,
+                    bar_type,
+                    bar_sub_type,
+                    &bar_type_specifier,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Minute bars with their volume profile between `start_index` and `finish_index` (unix
+    /// seconds), resumed until Rithmic delivered the whole range
+    pub async fn volume_profile_minute_bars(
+        &self,
+        symbol: &str,
+        exchange: &str,
+        bar_type_period: i32,
+        start_index: i32,
+        finish_index: i32,
+    ) -> Result<Vec<ResponseVolumeProfileMinuteBars>, String> {
+        let symbol = symbol.to_string();
+        let exchange = exchange.to_string();
+
+        let bars = self.stream_replay(
+            vec![(start_index, finish_index)],
+            move |start_index, finish_index, response_sender| {
+                HistoryPlantCommand::GetVolumeProfileMinuteBars {
+                    symbol: symbol.clone(),
+                    exchange: exchange.clone(),
+                    bar_type_period,
+                    start_index,
+                    finish_index,
+                    response_sender,
+                }
+            },
+        );
+
+        collect_replay(bars).await
+    }
+
+    /// Replays `windows` one after the other on a separate task
+    fn stream_replay<B: ReplayBar>(
+        &self,
+        windows: Vec<(i32, i32)>,
+        command: impl Fn(i32, i32, oneshot::Sender<Result<Vec<RithmicResponse>, String>>) -> HistoryPlantCommand
+            + Send
+            + Sync
+            + 'static,
+    ) -> mpsc::Receiver<Result<B, String>> {
+        let (tx, rx) = mpsc::channel(REPLAY_CHANNEL_CAPACITY);
+        let handle = self.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle.replay(windows, command, &tx).await {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+
+        rx
+    }
+
+    async fn replay<B: ReplayBar>(
+        &self,
+        windows: Vec<(i32, i32)>,
+        command: impl Fn(i32, i32, oneshot::Sender<Result<Vec<RithmicResponse>, String>>) -> HistoryPlantCommand,
+        tx: &mpsc::Sender<Result<B, String>>,
+    ) -> Result<(), String> {
+        // Bars on the edges of the previous window, Rithmic sends them again with the next one
+        let mut edge_bars = HashSet::new();
+
+        for (start_index, finish_index) in windows {
+            let mut responses = self
+                .request(|response_sender| command(start_index, finish_index, response_sender))
+                .await?;
+
+            let mut window_edge_bars = HashSet::new();
+
+            loop {
+                let mut request_key = None;
+                let mut page_len = 0;
+
+                for response in responses {
+                    let Some(bar) = B::from_message(response.message) else {
+                        continue;
+                    };
+
+                    if !bar.is_bar() {
+                        request_key = bar.request_key();
+
+                        continue;
+                    }
+
+                    page_len += 1;
+
+                    let key = bar.key();
+
+                    if edge_bars.contains(&key) {
+                        continue;
+                    }
+
+                    if key.0 == start_index || key.0 == finish_index {
+                        window_edge_bars.insert(key);
+                    }
+
+                    if tx.send(Ok(bar)).await.is_err() {
+                        // Nobody is listening anymore
+                        return Ok(());
+                    }
+                }
+
+                let Some(request_key) = request_key.filter(|_| page_len > 0) else {
+                    break;
+                };
+
+                event!(
+                    Level::DEBUG,
+                    "history_plant: resuming replay of {}-{} after {} bars",
+                    start_index,
+                    finish_index,
+                    page_len
+                );
+
+                responses = self
+                    .request(|response_sender| HistoryPlantCommand::ResumeBars {
+                        request_key,
+                        response_sender,
+                    })
+                    .await?;
+            }
+
+            edge_bars = window_edge_bars;
+        }
+
+        Ok(())
+    }
+}
+
+/// Length of the windows a tick bar replay is split into
+const TICK_BAR_WINDOW_SECONDS: i32 = 24 * 60 * 60;
+
+/// Bars buffered by a replay stream before the replay waits for the reader
+const REPLAY_CHANNEL_CAPACITY: usize = 4096;
+
+/// Live bars `backfill_and_subscribe` holds while the replay is running
+const MAX_BUFFERED_LIVE_BARS: usize = 4096;
+
+fn lagged_error(skipped: u64) -> String {
+    format!("history_plant: lagged, {} live bars skipped", skipped)
+}
+
+fn time_bar_window_seconds(bar_type: request_time_bar_replay::BarType, bar_type_period: i32) -> i32 {
+    let period = bar_type_period.max(1);
+
+    match bar_type {
+        request_time_bar_replay::BarType::SecondBar => period.saturating_mul(24 * 60 * 60),
+        request_time_bar_replay::BarType::MinuteBar => period.saturating_mul(30 * 24 * 60 * 60),
+        request_time_bar_replay::BarType::DailyBar | request_time_bar_replay::BarType::WeeklyBar => {
+            i32::MAX
+        }
+    }
+}
+
+/// Splits `start_index..=finish_index` in windows sharing their edges, latest first when not
+/// `forwards`
+fn replay_windows(start_index: i32, finish_index: i32, window: i32, forwards: bool) -> Vec<(i32, i32)> {
+    let mut windows = vec![];
+    let mut start = start_index;
+
+    loop {
+        let finish = start.saturating_add(window).min(finish_index);
+
+        windows.push((start, finish));
+
+        if finish >= finish_index {
+            break;
+        }
+
+        start = finish;
+    }
+
+    if !forwards {
+        windows.reverse();
+    }
+
+    windows
+}
+
+/// Converts the bars of a replay stream on a separate task
+fn map_replay<B: Send + 'static, T: From<B> + Send + 'static>(
+    mut bars: mpsc::Receiver<Result<B, String>>,
+) -> mpsc::Receiver<Result<T, String>> {
+    let (tx, rx) = mpsc::channel(REPLAY_CHANNEL_CAPACITY);
+
+    tokio::spawn(async move {
+        while let Some(bar) = bars.recv().await {
+            if tx.send(bar.map(T::from)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+async fn collect_replay<B>(mut bars: mpsc::Receiver<Result<B, String>>) -> Result<Vec<B>, String> {
+    let mut collected = vec![];
+
+    while let Some(bar) = bars.recv().await {
+        collected.push(bar?);
+    }
+
+    Ok(collected)
+}
+
+/// Timestamp, microseconds, volume, trades, open and close of a bar
+type ReplayBarKey = (i32, i32, u64, u64, u64, u64);
+
+/// Responses of a replay, either a bar or the end of a page
+trait ReplayBar: Send + 'static {
+    fn from_message(message: RithmicMessage) -> Option<Self>
+    where
+        Self: Sized;
+
+    fn is_bar(&self) -> bool;
 
-        Ok(rx.await.unwrap()?.remove(0))
+    fn request_key(&self) -> Option<String>;
+
+    fn key(&self) -> ReplayBarKey;
+}
+
+impl ReplayBar for ResponseTickBarReplay {
+    fn from_message(message: RithmicMessage) -> Option<Self> {
+        match message {
+            RithmicMessage::ResponseTickBarReplay(bar) => Some(bar),
+            _ => None,
+        }
+    }
+
+    fn is_bar(&self) -> bool {
+        self.rp_code.is_empty()
+    }
+
+    fn request_key(&self) -> Option<String> {
+        self.request_key.clone()
+    }
+
+    fn key(&self) -> ReplayBarKey {
+        (
+            self.data_bar_ssboe.first().copied().unwrap_or_default(),
+            self.data_bar_usecs.first().copied().unwrap_or_default(),
+            self.volume.unwrap_or_default(),
+            self.num_trades.unwrap_or_default(),
+            self.open_price.unwrap_or_default().to_bits(),
+            self.close_price.unwrap_or_default().to_bits(),
+        )
+    }
+}
+
+impl ReplayBar for ResponseTimeBarReplay {
+    fn from_message(message: RithmicMessage) -> Option<Self> {
+        match message {
+            RithmicMessage::ResponseTimeBarReplay(bar) => Some(bar),
+            _ => None,
+        }
+    }
+
+    fn is_bar(&self) -> bool {
+        self.rp_code.is_empty()
+    }
+
+    fn request_key(&self) -> Option<String> {
+        self.request_key.clone()
+    }
+
+    fn key(&self) -> ReplayBarKey {
+        (
+            self.marker.unwrap_or_default(),
+            0,
+            self.volume.unwrap_or_default(),
+            self.num_trades.unwrap_or_default(),
+            self.open_price.unwrap_or_default().to_bits(),
+            self.close_price.unwrap_or_default().to_bits(),
+        )
     }
 }
 
-impl Clone for RithmicHistoryPlantHandle {
-    fn clone(&self) -> Self {
-        RithmicHistoryPlantHandle {
-            sender: self.sender.clone(),
-            subscription_sender: self.subscription_sender.clone(),
-            subscription_receiver: self.subscription_sender.subscribe(),
+impl ReplayBar for ResponseVolumeProfileMinuteBars {
+    fn from_message(message: RithmicMessage) -> Option<Self> {
+        match message {
+            RithmicMessage::ResponseVolumeProfileMinuteBars(bar) => Some(bar),
+            _ => None,
         }
     }
+
+    fn is_bar(&self) -> bool {
+        self.rp_code.is_empty()
+    }
+
+    fn request_key(&self) -> Option<String> {
+        self.request_key.clone()
+    }
+
+    fn key(&self) -> ReplayBarKey {
+        (
+            self.marker.unwrap_or_default(),
+            0,
+            self.volume.unwrap_or_default(),
+            self.num_trades.unwrap_or_default(),
+            self.open_price.unwrap_or_default().to_bits(),
+            self.close_price.unwrap_or_default().to_bits(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plants::plant::PlantCommand;
+
+    const DAY: i32 = 24 * 60 * 60;
+
+    fn response(bar: ResponseTimeBarReplay) -> RithmicResponse {
+        RithmicResponse {
+            request_id: "".to_string(),
+            message: RithmicMessage::ResponseTimeBarReplay(bar),
+            is_update: false,
+            has_more: false,
+            multi_response: false,
+            error: None,
+            source: "history_plant".to_string(),
+        }
+    }
+
+    fn bar(marker: i32) -> RithmicResponse {
+        response(ResponseTimeBarReplay {
+            marker: Some(marker),
+            volume: Some(1),
+            num_trades: Some(1),
+            open_price: Some(100.0),
+            close_price: Some(100.0),
+            ..ResponseTimeBarReplay::default()
+        })
+    }
+
+    /// Last response of a page, resumed with `request_key` when set
+    fn end(request_key: Option<&str>) -> RithmicResponse {
+        response(ResponseTimeBarReplay {
+            rp_code: vec!["0".to_string()],
+            request_key: request_key.map(str::to_string),
+            ..ResponseTimeBarReplay::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn bar_on_the_boundary_of_two_windows_is_sent_once() {
+        let (handle, mut commands) = RithmicHistoryPlantHandle::detached("history_plant");
+
+        // The second window is resumed once, the boundary bar is on its first page
+        tokio::spawn(async move {
+            while let Some(PlantCommand::Plant(command)) = commands.recv().await {
+                match command {
+                    HistoryPlantCommand::GetHistoricalTimeBar {
+                        start_index,
+                        response_sender,
+                        ..
+                    } => {
+                        let responses = if start_index == 0 {
+                            vec![bar(0), bar(DAY / 2), bar(DAY), end(None)]
+                        } else {
+                            vec![bar(DAY), bar(DAY * 3 / 2), end(Some("page 2"))]
+                        };
+
+                        let _ = response_sender.send(Ok(responses));
+                    }
+                    HistoryPlantCommand::ResumeBars {
+                        request_key,
+                        response_sender,
+                    } => {
+                        assert_eq!(request_key, "page 2");
+
+                        let _ = response_sender.send(Ok(vec![bar(DAY * 2), end(None)]));
+                    }
+                    _ => panic!("unexpected command"),
+                }
+            }
+        });
+
+        let mut bars = handle.stream_historical_time_bar(
+            "ESM5".to_string(),
+            "CME".to_string(),
+            request_time_bar_replay::BarType::SecondBar,
+            1,
+            0,
+            DAY * 2,
+            request_time_bar_replay::Direction::First,
+            request_time_bar_replay::TimeOrder::Forwards,
+        );
+
+        let mut markers = vec![];
+
+        while let Some(bar) = bars.recv().await {
+            markers.push(bar.unwrap().marker.unwrap());
+        }
+
+        assert_eq!(markers, vec![0, DAY / 2, DAY, DAY * 3 / 2, DAY * 2]);
+    }
 }
@@ -1,53 +1,48 @@
+use std::{collections::VecDeque, time::Duration};
+
 use async_trait::async_trait;
-use tracing::{event, Level};
+use bytes::Bytes;
 
 use crate::{
     api::{
         RithmicConnectionInfo,
-        receiver_api::{RithmicReceiverApi, RithmicResponse},
-        rithmic_command_types::{RithmicBracketOrder, RithmicCancelOrder, RithmicModifyOrder},
-        sender_api::RithmicSenderApi,
+        receiver_api::RithmicResponse,
+        rithmic_command_types::{
+            RithmicAccount, RithmicBracketOrder, RithmicCancelOrder, RithmicModifyOrder,
+            RithmicOrder,
+        },
+        rithmic_rms_types::RithmicRiskLimits,
+    },
+    plants::plant::{PlantActorCore, PlantExtension, RithmicPlant, RithmicPlantHandle},
+    rti::{
+        ExchangeOrderNotification, ResponseAccountList, ResponseAccountRmsInfo, ResponseProductRmsInfo,
+        RithmicOrderNotification,
+        messages::RithmicMessage,
+        request_login::SysInfraType,
     },
-    request_handler::{RithmicRequest, RithmicRequestHandler},
-    rti::request_login::SysInfraType,
-    ws::{get_heartbeat_interval, PlantActor, RithmicStream, connect},
+    throttle::RithmicThrottleConfig,
 };
 
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt,
-};
-
-use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{Error, Message},
-    WebSocketStream,
-    MaybeTlsStream
-};
+use futures_util::{stream, Stream, StreamExt};
 
 use tokio::{
-    net::TcpStream,
-    sync::{broadcast::Sender, oneshot},
-    time::Interval,
+    sync::{mpsc, oneshot},
+    time::Instant,
 };
+use tracing::{event, Level};
 
 pub enum OrderPlantCommand {
-    Close,
-    Login {
-        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
-    },
-    SetLogin,
-    Logout {
-        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    SetAccount {
+        account: RithmicAccount,
     },
-    SendHeartbeat {},
     SubscribeOrderUpdates {
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
     SubscribeBracketUpdates {
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
-    SubscribePnlUpdates {
+    PlaceOrder {
+        order: RithmicOrder,
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
     PlaceBracketOrder {
@@ -75,265 +70,223 @@ pub enum OrderPlantCommand {
     ShowOrders {
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
+    AccountList {
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    AccountRmsInfo {
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ProductRmsInfo {
+        account_id: String,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    SubscribeAccountRmsUpdates {
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ReplayExecutions {
+        start_index: i32,
+        finish_index: i32,
+        /// Receives the replayed executions as they arrive
+        execution_sender: mpsc::UnboundedSender<RithmicResponse>,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ShowOrderHistoryDates {
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ShowOrderHistory {
+        basket_id: Option<String>,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ShowOrderHistorySummary {
+        date: String,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ShowOrderHistoryDetail {
+        basket_id: String,
+        date: String,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+}
+
+pub type RithmicOrderPlant = RithmicPlant<OrderPlant>;
+
+pub type RithmicOrderPlantHandle = RithmicPlantHandle<OrderPlantCommand>;
+
+/// Time without any answer after which a snapshot request fails and the next one is sent
+const SNAPSHOT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where the snapshot notifications of a request go
+#[derive(Clone)]
+enum SnapshotCapture {
+    /// To the subscribers, as any other order notification
+    Subscribers,
+    /// Returned with the response
+    Response,
+    /// Forwarded as they arrive, the channel closes with the request
+    Channel(mpsc::UnboundedSender<RithmicResponse>),
+}
+
+/// Request answered with snapshot order notifications ahead of its response
+struct SnapshotRequest {
+    request: (Bytes, String),
+    response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    capture: SnapshotCapture,
+}
+
+/// Snapshot request sent and not answered yet
+struct InFlightSnapshotRequest {
+    request_id: String,
+    capture: SnapshotCapture,
+    deadline: Instant,
 }
 
-pub struct RithmicOrderPlant {
-    pub connection_handle: tokio::task::JoinHandle<()>,
-    sender: tokio::sync::mpsc::Sender<OrderPlantCommand>,
-    subscription_sender: Sender<RithmicResponse>,
+/// Snapshot requests waiting for the one in flight
+#[derive(Default)]
+struct SnapshotQueue {
+    in_flight: Option<InFlightSnapshotRequest>,
+    queued: VecDeque<SnapshotRequest>,
 }
 
-impl RithmicOrderPlant {
-    pub async fn new(conn_info: &RithmicConnectionInfo) -> RithmicOrderPlant {
-        let (req_tx, req_rx) = tokio::sync::mpsc::channel::<OrderPlantCommand>(32);
-        let (sub_tx, _sub_rx) = tokio::sync::broadcast::channel(1024);
+impl SnapshotQueue {
+    fn push(&mut self, request: SnapshotRequest) {
+        self.queued.push_back(request);
+    }
 
-        let mut order_plant = OrderPlant::new(req_rx, sub_tx.clone(), conn_info)
-            .await
-            .unwrap();
+    /// Next request to send, None while one is in flight. The request in flight is dropped
+    /// first when it is not pending anymore, because it failed or its caller is gone.
+    fn next(&mut self, is_pending: impl Fn(&str) -> bool) -> Option<SnapshotRequest> {
+        if self
+            .in_flight
+            .as_ref()
+            .is_some_and(|in_flight| !is_pending(&in_flight.request_id))
+        {
+            self.in_flight = None;
+        }
+
+        if self.in_flight.is_some() {
+            return None;
+        }
 
-        let connection_handle = tokio::spawn(async move {
-            order_plant.run().await;
+        self.queued.pop_front()
+    }
+
+    fn start(&mut self, request_id: String, capture: SnapshotCapture, now: Instant) {
+        self.in_flight = Some(InFlightSnapshotRequest {
+            request_id,
+            capture,
+            deadline: now + SNAPSHOT_REQUEST_TIMEOUT,
         });
+    }
 
-        RithmicOrderPlant {
-            connection_handle,
-            sender: req_tx,
-            subscription_sender: sub_tx,
+    /// Pushes the deadline back, the request in flight is still being answered
+    fn touch(&mut self, now: Instant) {
+        if let Some(in_flight) = &mut self.in_flight {
+            in_flight.deadline = now + SNAPSHOT_REQUEST_TIMEOUT;
         }
     }
-}
 
-impl RithmicStream for RithmicOrderPlant {
-    type Handle = RithmicOrderPlantHandle;
+    fn finish(&mut self) {
+        self.in_flight = None;
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.in_flight.as_ref().map(|in_flight| in_flight.deadline)
+    }
 
-    fn get_handle(&self) -> RithmicOrderPlantHandle {
-        RithmicOrderPlantHandle {
-            sender: self.sender.clone(),
-            subscription_receiver: self.subscription_sender.subscribe(),
+    /// Id of the request in flight when its deadline passed, it is not in flight anymore
+    fn expire(&mut self, now: Instant) -> Option<String> {
+        if self.deadline().is_some_and(|deadline| deadline <= now) {
+            return self.in_flight.take().map(|in_flight| in_flight.request_id);
         }
+
+        None
     }
 }
 
+/// Snapshot notifications carry no request id, so the requests producing them are sent one at a
+/// time and the notifications belong to the one in flight
+#[derive(Default)]
 pub struct OrderPlant {
-    config: RithmicConnectionInfo,
-    interval: Interval,
-    logged_in: bool,
-    request_handler: RithmicRequestHandler,
-    request_receiver: tokio::sync::mpsc::Receiver<OrderPlantCommand>,
-    rithmic_reader: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    rithmic_receiver_api: RithmicReceiverApi,
-    rithmic_sender: SplitSink<
-        WebSocketStream<MaybeTlsStream<TcpStream>>,
-        Message,
-    >,
-    rithmic_sender_api: RithmicSenderApi,
-    subscription_sender: Sender<RithmicResponse>,
+    snapshot_requests: SnapshotQueue,
 }
 
 impl OrderPlant {
-    pub async fn new(
-        request_receiver: tokio::sync::mpsc::Receiver<OrderPlantCommand>,
-        subscription_sender: Sender<RithmicResponse>,
-        conn_info: &RithmicConnectionInfo,
-    ) -> Result<OrderPlant, String> {
-        let config = conn_info.clone();
-
-        let ws_stream = connect(&config.url).await.unwrap();
-        let (rithmic_sender, rithmic_reader) = ws_stream.split();
-        let rithmic_sender_api = RithmicSenderApi::new(&config);
-        let rithmic_receiver_api = RithmicReceiverApi {
-            source: "order_plant".to_string(),
-        };
+    async fn send_snapshot_request(&mut self, core: &mut PlantActorCore, request: SnapshotRequest) {
+        self.snapshot_requests.push(request);
 
-        let interval = get_heartbeat_interval();
-
-        Ok(OrderPlant {
-            config,
-            interval,
-            logged_in: false,
-            request_handler: RithmicRequestHandler::new(),
-            request_receiver,
-            rithmic_reader,
-            rithmic_receiver_api,
-            rithmic_sender_api,
-            rithmic_sender,
-            subscription_sender,
-        })
+        self.send_next_snapshot_request(core).await;
     }
-}
 
-#[async_trait]
-impl PlantActor for OrderPlant {
-    type Command = OrderPlantCommand;
+    async fn send_next_snapshot_request(&mut self, core: &mut PlantActorCore) {
+        while let Some(request) = self.snapshot_requests.next(|request_id| core.is_pending(request_id)) {
+            let request_id = request.request.1.clone();
 
-    async fn run(&mut self) {
-        loop {
-            tokio::select! {
-                _ = self.interval.tick() => {
-                    if self.logged_in {
-                        self.handle_command(OrderPlantCommand::SendHeartbeat {}).await;
-                    }
-                }
-                Some(message) = self.request_receiver.recv() => {
-                    self.handle_command(message).await;
-                }
-                Some(message) = self.rithmic_reader.next() => {
-                    let stop = self.handle_rithmic_message(message).await.unwrap();
+            core.send_request(request.request, request.response_sender, false).await;
 
-                    if stop {
-                        break;
-                    }
-                }
-                else => { break; }
+            // Not pending anymore when it could not be sent
+            if core.is_pending(&request_id) {
+                self.snapshot_requests.start(request_id, request.capture, Instant::now());
             }
         }
     }
+}
 
-    async fn handle_rithmic_message(
-        &mut self,
-        message: Result<Message, Error>,
-    ) -> Result<bool, ()> {
-        let mut stop: bool = false;
-
-        match message {
-            Ok(Message::Close(frame)) => {
-                event!(
-                    Level::INFO,
-                    "order_plant: Received close frame: {:?}",
-                    frame
-                );
-
-                stop = true;
-            }
-            Ok(Message::Binary(data)) => match self.rithmic_receiver_api.buf_to_message(data) {
-                Ok(response) => {
-                    if response.is_update {
-                        self.subscription_sender.send(response).unwrap();
-                    } else {
-                        self.request_handler.handle_response(response);
-                    }
-                }
-                Err(e) => {
-                    event!(Level::ERROR, "order_plant: response from server: {:?}", e);
-                }
-            },
-            Err(Error::ConnectionClosed) => {
-                event!(Level::INFO, "order_plant: Connection closed");
+#[async_trait]
+impl PlantExtension for OrderPlant {
+    type Command = OrderPlantCommand;
 
-                stop = true;
-            }
-            _ => {
-                event!(Level::WARN, "order_plant: Unhandled message: {:?}", message);
-            }
-        }
+    const NAME: &'static str = "order_plant";
+    const INFRA_TYPE: SysInfraType = SysInfraType::OrderPlant;
 
-        Ok(stop)
+    fn throttle(config: &RithmicConnectionInfo) -> Option<&RithmicThrottleConfig> {
+        config.order_throttle.as_ref()
     }
 
-    async fn handle_command(&mut self, command: OrderPlantCommand) {
+    async fn handle_command(&mut self, core: &mut PlantActorCore, command: OrderPlantCommand) {
         match command {
-            OrderPlantCommand::Close => {
-                self.rithmic_sender
-                    .send(Message::Close(None))
-                    .await
-                    .unwrap();
-            }
-            OrderPlantCommand::Login { response_sender } => {
-                let (login_buf, id) = self.rithmic_sender_api.request_login(
-                    &self.config.system_name,
-                    SysInfraType::OrderPlant,
-                    &self.config.user,
-                    &self.config.password,
-                );
-
-                event!(Level::INFO, "order_plant: sending login request {}", id);
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(login_buf))
-                    .await
-                    .unwrap();
-            }
-            OrderPlantCommand::SetLogin => {
-                self.logged_in = true;
-            }
-            OrderPlantCommand::Logout { response_sender } => {
-                let (logout_buf, id) = self.rithmic_sender_api.request_logout();
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(logout_buf))
-                    .await
-                    .unwrap();
-            }
-            OrderPlantCommand::SendHeartbeat {} => {
-                let (heartbeat_buf, _id) = self.rithmic_sender_api.request_heartbeat();
-
-                let _ = self
-                    .rithmic_sender
-                    .send(Message::Binary(heartbeat_buf))
-                    .await;
+            OrderPlantCommand::SetAccount { account } => {
+                core.rithmic_sender_api.set_account(&account);
             }
             OrderPlantCommand::SubscribeOrderUpdates { response_sender } => {
-                let (req_buf, id) = self
-                    .rithmic_sender_api
-                    .request_subscribe_for_order_updates();
+                let request = core.rithmic_sender_api.request_subscribe_for_order_updates();
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(req_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
             OrderPlantCommand::SubscribeBracketUpdates { response_sender } => {
-                let (req_buf, id) = self
-                    .rithmic_sender_api
-                    .request_subscribe_to_bracket_updates();
+                let request = core.rithmic_sender_api.request_subscribe_to_bracket_updates();
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
+                core.send_request(request, response_sender, false).await;
+            }
+            OrderPlantCommand::PlaceOrder {
+                order,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_new_order(
+                    &order.exchange,
+                    &order.symbol,
+                    order.qty,
+                    order.price,
+                    order.action,
+                    order.ordertype,
+                    &order.localid,
+                    order.duration,
+                );
 
-                self.rithmic_sender
-                    .send(Message::Binary(req_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
             OrderPlantCommand::PlaceBracketOrder {
                 bracket_order,
                 response_sender,
             } => {
-                let (req_buf, id) = self.rithmic_sender_api.request_bracket_order(bracket_order);
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
+                let request = core.rithmic_sender_api.request_bracket_order(bracket_order);
 
-                self.rithmic_sender
-                    .send(Message::Binary(req_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
             OrderPlantCommand::ModifyOrder {
                 order,
                 response_sender,
             } => {
-                let (req_buf, id) = self.rithmic_sender_api.request_modify_order(
+                let request = core.rithmic_sender_api.request_modify_order(
                     &order.id,
                     &order.exchange,
                     &order.symbol,
@@ -342,240 +295,635 @@ impl PlantActor for OrderPlant {
                     order.ordertype,
                 );
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(req_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
             OrderPlantCommand::CancelOrder {
                 order_id,
                 response_sender,
             } => {
-                let (req_buf, id) = self.rithmic_sender_api.request_cancel_order(&order_id);
+                let request = core.rithmic_sender_api.request_cancel_order(&order_id);
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(req_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, true).await;
             }
             OrderPlantCommand::ModifyStop {
                 order_id,
                 ticks,
                 response_sender,
             } => {
-                let (req_buf, id) = self
+                let request = core
                     .rithmic_sender_api
                     .request_update_stop_bracket_level(&order_id, ticks);
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(req_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
             OrderPlantCommand::ModifyProfit {
                 order_id,
                 ticks,
                 response_sender,
             } => {
-                let (req_buf, id) = self
+                let request = core
                     .rithmic_sender_api
                     .request_update_target_bracket_level(&order_id, ticks);
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(req_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
             OrderPlantCommand::ShowOrders { response_sender } => {
-                let (req_buf, id) = self.rithmic_sender_api.request_show_orders();
+                let request = core.rithmic_sender_api.request_show_orders();
+
+                // The open orders reach the subscribers, as any other order notification
+                self.send_snapshot_request(
+                    core,
+                    SnapshotRequest {
+                        request,
+                        response_sender,
+                        capture: SnapshotCapture::Subscribers,
+                    },
+                )
+                .await;
+            }
+            OrderPlantCommand::AccountList { response_sender } => {
+                let request = core.rithmic_sender_api.request_account_list();
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
+                core.send_request(request, response_sender, false).await;
+            }
+            OrderPlantCommand::AccountRmsInfo { response_sender } => {
+                let request = core.rithmic_sender_api.request_account_rms_info();
 
-                self.rithmic_sender
-                    .send(Message::Binary(req_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
-            _ => {}
-        };
-    }
-}
+            OrderPlantCommand::ProductRmsInfo {
+                account_id,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_product_rms_info(&account_id);
 
-pub struct RithmicOrderPlantHandle {
-    sender: tokio::sync::mpsc::Sender<OrderPlantCommand>,
-    pub subscription_receiver: tokio::sync::broadcast::Receiver<RithmicResponse>,
-}
+                core.send_request(request, response_sender, false).await;
+            }
+            OrderPlantCommand::SubscribeAccountRmsUpdates { response_sender } => {
+                let request = core.rithmic_sender_api.request_account_rms_updates(true);
 
-impl RithmicOrderPlantHandle {
-    pub async fn login(&self) -> Result<RithmicResponse, String> {
-        event!(Level::INFO, "order_plant: logging in");
+                core.send_request(request, response_sender, false).await;
+            }
+            OrderPlantCommand::ReplayExecutions {
+                start_index,
+                finish_index,
+                execution_sender,
+                response_sender,
+            } => {
+                let request = core
+                    .rithmic_sender_api
+                    .request_replay_executions(start_index, finish_index);
+
+                self.send_snapshot_request(
+                    core,
+                    SnapshotRequest {
+                        request,
+                        response_sender,
+                        capture: SnapshotCapture::Channel(execution_sender),
+                    },
+                )
+                .await;
+            }
+            OrderPlantCommand::ShowOrderHistoryDates { response_sender } => {
+                let request = core.rithmic_sender_api.request_show_order_history_dates();
 
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+                core.send_request(request, response_sender, false).await;
+            }
+            OrderPlantCommand::ShowOrderHistory {
+                basket_id,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_show_order_history(basket_id);
+
+                self.send_snapshot_request(
+                    core,
+                    SnapshotRequest {
+                        request,
+                        response_sender,
+                        capture: SnapshotCapture::Response,
+                    },
+                )
+                .await;
+            }
+            OrderPlantCommand::ShowOrderHistorySummary {
+                date,
+                response_sender,
+            } => {
+                let request = core
+                    .rithmic_sender_api
+                    .request_show_order_history_summary(&date);
+
+                self.send_snapshot_request(
+                    core,
+                    SnapshotRequest {
+                        request,
+                        response_sender,
+                        capture: SnapshotCapture::Response,
+                    },
+                )
+                .await;
+            }
+            OrderPlantCommand::ShowOrderHistoryDetail {
+                basket_id,
+                date,
+                response_sender,
+            } => {
+                let request = core
+                    .rithmic_sender_api
+                    .request_show_order_history_detail(&basket_id, &date);
+
+                self.send_snapshot_request(
+                    core,
+                    SnapshotRequest {
+                        request,
+                        response_sender,
+                        capture: SnapshotCapture::Response,
+                    },
+                )
+                .await;
+            }
+        }
+    }
 
-        let command = OrderPlantCommand::Login {
-            response_sender: tx,
+    async fn handle_response(
+        &mut self,
+        core: &mut PlantActorCore,
+        mut response: RithmicResponse,
+    ) -> Option<RithmicResponse> {
+        // Sends the next request when the one in flight failed or was dropped
+        self.send_next_snapshot_request(core).await;
+
+        let Some(in_flight) = &self.snapshot_requests.in_flight else {
+            return Some(response);
         };
 
-        let _ = self.sender.send(command).await;
-        let response = rx.await.unwrap().unwrap().remove(0);
+        let request_id = in_flight.request_id.clone();
+        let capture = in_flight.capture.clone();
 
-        if response.error.is_none() {
-            let _ = self.sender.send(OrderPlantCommand::SetLogin).await;
+        let is_snapshot = match &response.message {
+            RithmicMessage::RithmicOrderNotification(notification) => notification.is_snapshot,
+            RithmicMessage::ExchangeOrderNotification(notification) => notification.is_snapshot,
+            _ => None,
+        };
 
-            event!(Level::INFO, "order_plant: logged in");
+        if is_snapshot == Some(true) {
+            self.snapshot_requests.touch(Instant::now());
+
+            match capture {
+                SnapshotCapture::Subscribers => {}
+                SnapshotCapture::Response => {
+                    // Kept by the request handler until the response completes the request
+                    response.request_id = request_id;
+                    response.is_update = false;
+                    response.has_more = true;
+                    response.multi_response = true;
+                }
+                SnapshotCapture::Channel(sender) => {
+                    // The caller stopped reading, the request is dropped with its receiver
+                    let _ = sender.send(response);
 
-            Ok(response)
-        } else {
-            event!(
-                Level::ERROR,
-                "order_plant: login failed {:?}",
-                response.error
-            );
+                    return None;
+                }
+            }
+
+            return Some(response);
+        }
 
-            Err(response.error.unwrap())
+        if response.request_id == request_id {
+            self.snapshot_requests.touch(Instant::now());
         }
+
+        let completed = matches!(response.message, RithmicMessage::ForcedLogout(_))
+            || (response.request_id == request_id
+                && (!response.has_more
+                    || response.error.is_some()
+                    || matches!(response.message, RithmicMessage::Reject(_))));
+
+        if completed {
+            self.snapshot_requests.finish();
+            self.send_next_snapshot_request(core).await;
+        }
+
+        Some(response)
     }
 
-    pub async fn disconnect(&self) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+    fn deadline(&self) -> Option<Instant> {
+        self.snapshot_requests.deadline()
+    }
 
-        let command = OrderPlantCommand::Logout {
-            response_sender: tx,
-        };
+    async fn handle_deadline(&mut self, core: &mut PlantActorCore) {
+        if let Some(request_id) = self.snapshot_requests.expire(Instant::now()) {
+            event!(Level::WARN, "order_plant: snapshot request {} timed out", request_id);
 
-        let _ = self.sender.send(command).await;
-        let mut r = rx.await.unwrap().unwrap();
-        let _ = self.sender.send(OrderPlantCommand::Close).await;
+            core.fail_request(
+                &request_id,
+                &format!("no answer within {:?}", SNAPSHOT_REQUEST_TIMEOUT),
+            );
+        }
 
-        Ok(r.remove(0))
+        self.send_next_snapshot_request(core).await;
     }
+}
 
-    pub async fn subscribe_order_updates(&self) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+/// Orders replayed by one of the order history requests
+///
+/// Rithmic sends the history as snapshot order notifications ahead of the response
+#[derive(Debug, Clone, Default)]
+pub struct RithmicOrderHistory {
+    pub rithmic_order_notifications: Vec<RithmicOrderNotification>,
+    pub exchange_order_notifications: Vec<ExchangeOrderNotification>,
+}
 
-        let command = OrderPlantCommand::SubscribeOrderUpdates {
-            response_sender: tx,
-        };
+impl From<Vec<RithmicResponse>> for RithmicOrderHistory {
+    fn from(responses: Vec<RithmicResponse>) -> RithmicOrderHistory {
+        let mut history = RithmicOrderHistory::default();
 
-        let _ = self.sender.send(command).await;
+        for response in responses {
+            match response.message {
+                RithmicMessage::RithmicOrderNotification(notification) => {
+                    history.rithmic_order_notifications.push(notification);
+                }
+                RithmicMessage::ExchangeOrderNotification(notification) => {
+                    history.exchange_order_notifications.push(notification);
+                }
+                _ => {}
+            }
+        }
 
-        Ok(rx.await.unwrap().unwrap().remove(0))
+        history
     }
+}
 
-    pub async fn subscribe_bracket_updates(&self) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+impl RithmicPlantHandle<OrderPlantCommand> {
+    pub async fn subscribe_order_updates(&self) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| OrderPlantCommand::SubscribeOrderUpdates { response_sender })
+            .await?;
 
-        let command = OrderPlantCommand::SubscribeBracketUpdates {
-            response_sender: tx,
-        };
+        Ok(r.remove(0))
+    }
 
-        let _ = self.sender.send(command).await;
+    pub async fn subscribe_bracket_updates(&self) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| OrderPlantCommand::SubscribeBracketUpdates { response_sender })
+            .await?;
 
-        Ok(rx.await.unwrap().unwrap().remove(0))
+        Ok(r.remove(0))
+    }
+
+    pub async fn place_order(&self, order: RithmicOrder) -> Result<Vec<RithmicResponse>, String> {
+        self.request(|response_sender| OrderPlantCommand::PlaceOrder {
+            order,
+            response_sender,
+        })
+        .await
     }
 
     pub async fn place_bracket_order(
         &self,
         bracket_order: RithmicBracketOrder,
     ) -> Result<Vec<RithmicResponse>, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = OrderPlantCommand::PlaceBracketOrder {
+        self.request(|response_sender| OrderPlantCommand::PlaceBracketOrder {
             bracket_order,
-            response_sender: tx,
-        };
+            response_sender,
+        })
+        .await
+    }
 
-        let _ = self.sender.send(command).await;
+    /// Places every piece produced by `RithmicBracketOrderBuilder::build`, stopping at the first
+    /// rejected piece
+    pub async fn place_bracket_orders(
+        &self,
+        bracket_orders: Vec<RithmicBracketOrder>,
+    ) -> Result<Vec<RithmicResponse>, String> {
+        let mut responses = vec![];
+
+        for bracket_order in bracket_orders {
+            responses.extend(self.place_bracket_order(bracket_order).await?);
+        }
 
-        rx.await.unwrap()
+        Ok(responses)
     }
 
     pub async fn modify_order(&self, order: RithmicModifyOrder) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+        let mut r = self
+            .request(|response_sender| OrderPlantCommand::ModifyOrder {
+                order,
+                response_sender,
+            })
+            .await?;
 
-        let command = OrderPlantCommand::ModifyOrder {
-            order,
-            response_sender: tx,
-        };
+        Ok(r.remove(0))
+    }
 
-        let _ = self.sender.send(command).await;
+    pub async fn cancel_order(&self, order: RithmicCancelOrder) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| OrderPlantCommand::CancelOrder {
+                order_id: order.id,
+                response_sender,
+            })
+            .await?;
 
-        Ok(rx.await.unwrap().unwrap().remove(0))
+        Ok(r.remove(0))
     }
 
-    pub async fn cancel_order(&self, order: RithmicCancelOrder) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+    pub async fn adjust_profit(&self, id: &str, ticks: i32) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| OrderPlantCommand::ModifyProfit {
+                order_id: id.to_string(),
+                ticks,
+                response_sender,
+            })
+            .await?;
 
-        let command = OrderPlantCommand::CancelOrder {
-            order_id: order.id,
-            response_sender: tx,
-        };
+        Ok(r.remove(0))
+    }
 
-        let _ = self.sender.send(command).await;
+    pub async fn adjust_stop(&self, id: &str, ticks: i32) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| OrderPlantCommand::ModifyStop {
+                order_id: id.to_string(),
+                ticks,
+                response_sender,
+            })
+            .await?;
 
-        Ok(rx.await.unwrap().unwrap().remove(0))
+        Ok(r.remove(0))
     }
 
-    pub async fn adjust_profit(&self, id: &str, ticks: i32) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+    pub async fn show_orders(&self) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| OrderPlantCommand::ShowOrders { response_sender })
+            .await?;
 
-        let command = OrderPlantCommand::ModifyProfit {
-            order_id: id.to_string(),
-            ticks,
-            response_sender: tx,
-        };
+        Ok(r.remove(0))
+    }
 
-        let _ = self.sender.send(command).await;
+    /// Accounts the user can trade, the fcm and ib ids come with each account
+    pub async fn account_list(&self) -> Result<Vec<ResponseAccountList>, String> {
+        let accounts = self
+            .request(|response_sender| OrderPlantCommand::AccountList { response_sender })
+            .await?
+            .into_iter()
+            .filter_map(|response| match response.message {
+                RithmicMessage::ResponseAccountList(resp) if resp.account_id.is_some() => {
+                    Some(resp)
+                }
+                _ => None,
+            })
+            .collect();
 
-        Ok(rx.await.unwrap().unwrap().remove(0))
+        Ok(accounts)
     }
 
-    pub async fn adjust_stop(&self, id: &str, ticks: i32) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+    /// Sets the account used by the order, rms and history requests
+    pub async fn set_account(&self, account: &RithmicAccount) {
+        self.send_command(OrderPlantCommand::SetAccount {
+            account: account.clone(),
+        })
+        .await;
+    }
 
-        let command = OrderPlantCommand::ModifyStop {
-            order_id: id.to_string(),
-            ticks,
-            response_sender: tx,
-        };
+    pub async fn account_rms_info(&self) -> Result<Vec<ResponseAccountRmsInfo>, String> {
+        let infos = self
+            .request(|response_sender| OrderPlantCommand::AccountRmsInfo { response_sender })
+            .await?
+            .into_iter()
+            .filter_map(|response| match response.message {
+                RithmicMessage::ResponseAccountRmsInfo(resp) if resp.account_id.is_some() => {
+                    Some(resp)
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(infos)
+    }
+
+    pub async fn product_rms_info(
+        &self,
+        account_id: &str,
+    ) -> Result<Vec<ResponseProductRmsInfo>, String> {
+        let infos = self
+            .request(|response_sender| OrderPlantCommand::ProductRmsInfo {
+                account_id: account_id.to_string(),
+                response_sender,
+            })
+            .await?
+            .into_iter()
+            .filter_map(|response| match response.message {
+                RithmicMessage::ResponseProductRmsInfo(resp) if resp.product_code.is_some() => {
+                    Some(resp)
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(infos)
+    }
 
-        let _ = self.sender.send(command).await;
+    /// Account and product limits of `account_id` combined in a single view
+    pub async fn risk_limits(&self, account_id: &str) -> Result<RithmicRiskLimits, String> {
+        let account = self
+            .account_rms_info()
+            .await?
+            .into_iter()
+            .find(|info| info.account_id.as_deref() == Some(account_id))
+            .ok_or_else(|| format!("order_plant: no rms info for account {}", account_id))?;
 
-        Ok(rx.await.unwrap().unwrap().remove(0))
+        let products = self.product_rms_info(account_id).await?;
+
+        Ok(RithmicRiskLimits::from(&account).with_products(&products))
     }
 
-    pub async fn show_orders(&self) -> Result<RithmicResponse, String> {
+    /// Updates are sent to the subscription receiver as `AccountRmsUpdates`
+    pub async fn subscribe_account_rms_updates(&self) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| OrderPlantCommand::SubscribeAccountRmsUpdates {
+                response_sender,
+            })
+            .await?;
+
+        Ok(r.remove(0))
+    }
+
+    pub async fn order_history_dates(&self) -> Result<Vec<String>, String> {
+        let dates = self
+            .request(|response_sender| OrderPlantCommand::ShowOrderHistoryDates {
+                response_sender,
+            })
+            .await?
+            .into_iter()
+            .flat_map(|response| match response.message {
+                RithmicMessage::ResponseShowOrderHistoryDates(resp) => resp.date,
+                _ => vec![],
+            })
+            .collect();
+
+        Ok(dates)
+    }
+
+    /// Orders of the account for a date (`YYYYMMDD`)
+    pub async fn order_history_summary(&self, date: &str) -> Result<RithmicOrderHistory, String> {
+        let responses = self
+            .request(|response_sender| OrderPlantCommand::ShowOrderHistorySummary {
+                date: date.to_string(),
+                response_sender,
+            })
+            .await?;
+
+        Ok(RithmicOrderHistory::from(responses))
+    }
+
+    /// History of a single order for the current session
+    pub async fn order_history(&self, basket_id: &str) -> Result<RithmicOrderHistory, String> {
+        let responses = self
+            .request(|response_sender| OrderPlantCommand::ShowOrderHistory {
+                basket_id: Some(basket_id.to_string()),
+                response_sender,
+            })
+            .await?;
+
+        Ok(RithmicOrderHistory::from(responses))
+    }
+
+    /// History of a single order for a date (`YYYYMMDD`)
+    pub async fn order_history_detail(
+        &self,
+        basket_id: &str,
+        date: &str,
+    ) -> Result<RithmicOrderHistory, String> {
+        let responses = self
+            .request(|response_sender| OrderPlantCommand::ShowOrderHistoryDetail {
+                basket_id: basket_id.to_string(),
+                date: date.to_string(),
+                response_sender,
+            })
+            .await?;
+
+        Ok(RithmicOrderHistory::from(responses))
+    }
+
+    /// Replays the account executions between `start_index` and `finish_index` (seconds since
+    /// epoch).
+    ///
+    /// The executions are yielded as Rithmic sends them, the stream ends once it reports the
+    /// replay as complete, with an error when the replay failed.
+    pub async fn replay_executions(
+        &self,
+        start_index: i32,
+        finish_index: i32,
+    ) -> impl Stream<Item = Result<ExchangeOrderNotification, String>> + use<> {
+        let (execution_sender, execution_receiver) = mpsc::unbounded_channel();
         let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
 
-        let command = OrderPlantCommand::ShowOrders {
+        self.send_command(OrderPlantCommand::ReplayExecutions {
+            start_index,
+            finish_index,
+            execution_sender,
             response_sender: tx,
+        })
+        .await;
+
+        let executions = stream::unfold(execution_receiver, |mut receiver| async move {
+            receiver.recv().await.map(|response| (response, receiver))
+        })
+        .filter_map(|response| async move {
+            match response.message {
+                RithmicMessage::ExchangeOrderNotification(notification) => Some(Ok(notification)),
+                _ => None,
+            }
+        });
+
+        // The executions channel closes before the response is sent
+        let completion = stream::once(rx).filter_map(|result| async move {
+            match result {
+                Ok(Ok(_)) => None,
+                Ok(Err(e)) => Some(Err(e)),
+                Err(_) => Some(Err("order_plant: connection closed".to_string())),
+            }
+        });
+
+        executions.chain(completion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot_request(
+        request_id: &str,
+    ) -> (SnapshotRequest, oneshot::Receiver<Result<Vec<RithmicResponse>, String>>) {
+        let (response_sender, response_receiver) = oneshot::channel();
+
+        let request = SnapshotRequest {
+            request: (Bytes::new(), request_id.to_string()),
+            response_sender,
+            capture: SnapshotCapture::Response,
         };
 
-        let _ = self.sender.send(command).await;
+        (request, response_receiver)
+    }
+
+    #[test]
+    fn snapshot_requests_are_sent_one_at_a_time() {
+        let mut queue = SnapshotQueue::default();
+        let now = Instant::now();
+
+        let (first, _first_receiver) = snapshot_request("1");
+        let (second, _second_receiver) = snapshot_request("2");
+
+        queue.push(first);
+        queue.push(second);
+
+        assert_eq!(queue.next(|_| true).map(|r| r.request.1), Some("1".to_string()));
+
+        queue.start("1".to_string(), SnapshotCapture::Response, now);
+
+        assert!(queue.next(|_| true).is_none());
+
+        queue.finish();
+
+        assert_eq!(queue.next(|_| true).map(|r| r.request.1), Some("2".to_string()));
+    }
+
+    #[test]
+    fn queued_request_is_sent_after_a_failed_one() {
+        let mut queue = SnapshotQueue::default();
+        let now = Instant::now();
+
+        let (second, _second_receiver) = snapshot_request("2");
+
+        queue.start("1".to_string(), SnapshotCapture::Response, now);
+        queue.push(second);
+
+        assert!(queue.next(|_| true).is_none());
+
+        // Failed, or dropped by its caller, the request handler does not wait for it anymore
+        assert_eq!(
+            queue.next(|request_id| request_id != "1").map(|r| r.request.1),
+            Some("2".to_string())
+        );
+    }
+
+    #[test]
+    fn unanswered_request_expires() {
+        let mut queue = SnapshotQueue::default();
+        let now = Instant::now();
+
+        let (second, _second_receiver) = snapshot_request("2");
+
+        queue.start("1".to_string(), SnapshotCapture::Response, now);
+        queue.push(second);
+
+        assert_eq!(queue.deadline(), Some(now + SNAPSHOT_REQUEST_TIMEOUT));
+        assert_eq!(queue.expire(now + SNAPSHOT_REQUEST_TIMEOUT / 2), None);
+
+        queue.touch(now + SNAPSHOT_REQUEST_TIMEOUT / 2);
 
-        Ok(rx.await.unwrap().unwrap().remove(0))
+        assert_eq!(queue.expire(now + SNAPSHOT_REQUEST_TIMEOUT), None);
+        assert_eq!(queue.expire(now + SNAPSHOT_REQUEST_TIMEOUT * 2), Some("1".to_string()));
+        assert_eq!(queue.next(|_| true).map(|r| r.request.1), Some("2".to_string()));
     }
 }
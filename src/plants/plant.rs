@@ -0,0 +1,644 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use tracing::{event, Level};
+
+use crate::{
+    api::{
+        RithmicConnectionInfo,
+        receiver_api::{RithmicReceiverApi, RithmicResponse},
+        sender_api::RithmicSenderApi,
+    },
+    request_handler::{RithmicRequest, RithmicRequestHandler},
+    rti::{messages::RithmicMessage, request_login::SysInfraType},
+    throttle::{RithmicThrottle, RithmicThrottleConfig, RithmicThrottleStats},
+    ws::{get_heartbeat_interval, PlantActor, RithmicSessionEvent, RithmicStream, connect},
+};
+
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt,
+};
+
+use tokio_tungstenite::{
+    tungstenite::{Error, Message},
+    WebSocketStream,
+    MaybeTlsStream
+};
+
+use tokio::{
+    net::TcpStream,
+    sync::{
+        broadcast::{self, Sender},
+        mpsc, oneshot,
+    },
+    time::{sleep_until, Instant, Interval},
+};
+
+/// Commands understood by every plant, `Plant` wraps the commands of a `PlantExtension`
+pub enum PlantCommand<C> {
+    Close,
+    Login {
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    Logout {
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    SendHeartbeat {},
+    SetLogin,
+    ThrottleStats {
+        response_sender: oneshot::Sender<Option<RithmicThrottleStats>>,
+    },
+    Plant(C),
+}
+
+/// What a plant adds to the shared core: the infrastructure it logs into and its own commands.
+///
+/// Connection, login, heartbeats, throttling, request tracking and session events are handled by
+/// `PlantActorCore`, so a custom plant only has to turn its commands into requests:
+///
+/// ```ignore
+/// enum ProductPlantCommand {
+///     ProductCodes {
+///         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+///     },
+/// }
+///
+/// #[derive(Default)]
+/// struct ProductPlant;
+///
+/// #[async_trait]
+/// impl PlantExtension for ProductPlant {
+///     type Command = ProductPlantCommand;
+///
+///     const NAME: &'static str = "product_plant";
+///     const INFRA_TYPE: SysInfraType = SysInfraType::TickerPlant;
+///
+///     async fn handle_command(&mut self, core: &mut PlantActorCore, command: ProductPlantCommand) {
+///         match command {
+///             ProductPlantCommand::ProductCodes { response_sender } => {
+///                 let request = core.rithmic_sender_api.request_product_codes(None);
+///
+///                 core.send_request(request, response_sender, false).await;
+///             }
+///         }
+///     }
+/// }
+///
+/// let plant = RithmicPlant::<ProductPlant>::try_new(&conn_info).await?;
+/// let product_codes = plant
+///     .get_handle()
+///     .request(|response_sender| ProductPlantCommand::ProductCodes { response_sender })
+///     .await?;
+/// ```
+#[async_trait]
+pub trait PlantExtension: Send + 'static {
+    type Command: Send + 'static;
+
+    /// Used in the logs and as the source of the responses, e.g. `ticker_plant`
+    const NAME: &'static str;
+    const INFRA_TYPE: SysInfraType;
+
+    async fn handle_command(&mut self, core: &mut PlantActorCore, command: Self::Command);
+
+    /// Rate limit of the requests of this plant, unlimited when None
+    fn throttle(_config: &RithmicConnectionInfo) -> Option<&RithmicThrottleConfig> {
+        None
+    }
+
+    /// Sees every message from Rithmic before the request handler or the subscribers do, None
+    /// keeps it from them
+    async fn handle_response(
+        &mut self,
+        _core: &mut PlantActorCore,
+        response: RithmicResponse,
+    ) -> Option<RithmicResponse> {
+        Some(response)
+    }
+
+    /// When `handle_deadline` should run next, None when nothing is waiting for it
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    async fn handle_deadline(&mut self, _core: &mut PlantActorCore) {}
+}
+
+/// Owns the plant task, `get_handle` gives handles talking to it
+pub struct RithmicPlant<E: PlantExtension> {
+    pub connection_handle: tokio::task::JoinHandle<()>,
+    sender: mpsc::Sender<PlantCommand<E::Command>>,
+    subscription_sender: Sender<RithmicResponse>,
+}
+
+impl<E: PlantExtension + Default> RithmicPlant<E> {
+    pub async fn new(conn_info: &RithmicConnectionInfo) -> RithmicPlant<E> {
+        RithmicPlant::try_new(conn_info).await.unwrap()
+    }
+
+    /// Same as `new`, returning an error when the connection fails
+    pub async fn try_new(conn_info: &RithmicConnectionInfo) -> Result<RithmicPlant<E>, String> {
+        RithmicPlant::spawn(conn_info, E::default()).await
+    }
+}
+
+impl<E: PlantExtension> RithmicPlant<E> {
+    /// Connects and runs the plant with `extension` in its own task
+    pub async fn spawn(
+        conn_info: &RithmicConnectionInfo,
+        extension: E,
+    ) -> Result<RithmicPlant<E>, String> {
+        let (req_tx, req_rx) = mpsc::channel::<PlantCommand<E::Command>>(32);
+        let (sub_tx, _sub_rx) = broadcast::channel(1024);
+
+        let mut plant = Plant::new(req_rx, sub_tx.clone(), conn_info, extension).await?;
+
+        let connection_handle = tokio::spawn(async move {
+            plant.run().await;
+        });
+
+        Ok(RithmicPlant {
+            connection_handle,
+            sender: req_tx,
+            subscription_sender: sub_tx,
+        })
+    }
+}
+
+impl<E: PlantExtension> RithmicStream for RithmicPlant<E> {
+    type Handle = RithmicPlantHandle<E::Command>;
+
+    fn get_handle(&self) -> RithmicPlantHandle<E::Command> {
+        RithmicPlantHandle {
+            name: E::NAME,
+            sender: self.sender.clone(),
+            subscription_sender: self.subscription_sender.clone(),
+            subscription_receiver: self.subscription_sender.subscribe(),
+        }
+    }
+}
+
+/// State shared by every plant, passed to `PlantExtension::handle_command`
+pub struct PlantActorCore {
+    pub config: RithmicConnectionInfo,
+    pub rithmic_sender_api: RithmicSenderApi,
+    logged_in: bool,
+    request_handler: RithmicRequestHandler,
+    rithmic_receiver_api: RithmicReceiverApi,
+    rithmic_sender: SplitSink<
+        WebSocketStream<MaybeTlsStream<TcpStream>>,
+        Message,
+    >,
+    subscription_sender: Sender<RithmicResponse>,
+    throttle: Option<RithmicThrottle>,
+}
+
+impl PlantActorCore {
+    pub fn name(&self) -> &str {
+        &self.rithmic_receiver_api.source
+    }
+
+    pub fn is_logged_in(&self) -> bool {
+        self.logged_in
+    }
+
+    /// Whether a request sent with `send_request` is still waiting for its response, false once
+    /// its caller stopped waiting
+    pub fn is_pending(&self, request_id: &str) -> bool {
+        self.request_handler.is_pending(request_id)
+    }
+
+    /// Completes a pending request with an error
+    pub fn fail_request(&mut self, request_id: &str, reason: &str) {
+        self.request_handler.fail_request(request_id, reason);
+    }
+
+    /// Registers the request so its responses reach `response_sender`, then sends it
+    pub async fn send_request(
+        &mut self,
+        (buf, request_id): (Bytes, String),
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+        priority: bool,
+    ) {
+        self.request_handler.register_request(RithmicRequest {
+            request_id: request_id.clone(),
+            responder: response_sender,
+        });
+
+        if let Err(e) = self.send_throttled(buf, priority).await {
+            event!(Level::WARN, "{}: request {} not sent: {}", self.name(), request_id, e);
+
+            self.request_handler.fail_request(&request_id, &e);
+        }
+    }
+
+    /// Sends a request right away, or queues it when the plant is throttled. Fails when the
+    /// throttle queue is full.
+    pub async fn send_throttled(&mut self, buf: Bytes, priority: bool) -> Result<(), String> {
+        match self.throttle.as_mut() {
+            Some(throttle) => throttle.push(Message::Binary(buf), priority),
+            None => {
+                self.send(Message::Binary(buf)).await;
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends to the subscribers of the plant
+    pub fn send_update(&self, response: RithmicResponse) {
+        if self.subscription_sender.send(response).is_err() {
+            event!(Level::DEBUG, "{}: no subscriber for update", self.name());
+        }
+    }
+
+    async fn send(&mut self, message: Message) {
+        if let Err(e) = self.rithmic_sender.send(message).await {
+            event!(Level::ERROR, "{}: failed to send message {:?}", self.name(), e);
+        }
+    }
+
+    async fn flush_throttled(&mut self) {
+        while let Some(message) = self.throttle.as_mut().and_then(|t| t.pop()) {
+            self.send(message).await;
+        }
+    }
+
+    fn send_session_event(&self, session_event: RithmicSessionEvent) {
+        self.send_update(RithmicResponse {
+            request_id: "".to_string(),
+            message: RithmicMessage::SessionEvent(session_event),
+            is_update: true,
+            has_more: false,
+            multi_response: false,
+            error: None,
+            source: self.rithmic_receiver_api.source.clone(),
+        });
+    }
+
+    /// Fails the rejected request, or the whole session on a forced logout
+    fn handle_session_message(&mut self, response: RithmicResponse) {
+        match response.message {
+            RithmicMessage::Reject(_) => {
+                let reason = response
+                    .error
+                    .clone()
+                    .unwrap_or_else(|| "rejected by server".to_string());
+
+                event!(
+                    Level::ERROR,
+                    "{}: request {} rejected: {}",
+                    self.name(),
+                    response.request_id,
+                    reason
+                );
+
+                self.request_handler.fail_request(&response.request_id, &reason);
+                self.send_session_event(RithmicSessionEvent::Rejected {
+                    request_id: response.request_id,
+                    reason: response.error,
+                });
+            }
+            RithmicMessage::ForcedLogout(_) => {
+                event!(Level::WARN, "{}: forced logout from server", self.name());
+
+                self.logged_in = false;
+                self.request_handler.fail_all("forced logout from server");
+                self.send_session_event(RithmicSessionEvent::ForcedLogout);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_connection_closed(&mut self) {
+        self.logged_in = false;
+        self.request_handler.fail_all("connection closed");
+        self.send_session_event(RithmicSessionEvent::ConnectionClosed);
+    }
+}
+
+/// Plant actor running the shared commands and the commands of `E`
+pub struct Plant<E: PlantExtension> {
+    core: PlantActorCore,
+    extension: E,
+    interval: Interval,
+    request_receiver: mpsc::Receiver<PlantCommand<E::Command>>,
+    rithmic_reader: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+}
+
+impl<E: PlantExtension> Plant<E> {
+    async fn new(
+        request_receiver: mpsc::Receiver<PlantCommand<E::Command>>,
+        subscription_sender: Sender<RithmicResponse>,
+        conn_info: &RithmicConnectionInfo,
+        extension: E,
+    ) -> Result<Plant<E>, String> {
+        let config = conn_info.clone();
+
+        let throttle = E::throttle(&config)
+            .map(RithmicThrottle::new)
+            .transpose()
+            .map_err(|e| format!("{}: {}", E::NAME, e))?;
+
+        let ws_stream = connect(&config.url, config.proxy.as_ref(), config.tls.as_ref())
+            .await
+            .map_err(|e| format!("{}: failed to connect to {}: {}", E::NAME, config.url, e))?;
+        let (rithmic_sender, rithmic_reader) = ws_stream.split();
+        let rithmic_sender_api = RithmicSenderApi::new(&config);
+        let rithmic_receiver_api = RithmicReceiverApi {
+            source: E::NAME.to_string(),
+        };
+
+        let interval = get_heartbeat_interval();
+
+        Ok(Plant {
+            core: PlantActorCore {
+                config,
+                rithmic_sender_api,
+                logged_in: false,
+                request_handler: RithmicRequestHandler::new(),
+                rithmic_receiver_api,
+                rithmic_sender,
+                subscription_sender,
+                throttle,
+            },
+            extension,
+            interval,
+            request_receiver,
+            rithmic_reader,
+        })
+    }
+}
+
+#[async_trait]
+impl<E: PlantExtension> PlantActor for Plant<E> {
+    type Command = PlantCommand<E::Command>;
+
+    /// Listens for commands from the handles and forwards them to Rithmic, while forwarding the
+    /// messages from Rithmic to the subscribers or the request handler
+    async fn run(&mut self) {
+        loop {
+            let deadline = self.extension.deadline();
+
+            tokio::select! {
+                _ = self.interval.tick() => {
+                    if self.core.logged_in {
+                        self.handle_command(PlantCommand::SendHeartbeat {}).await;
+                    }
+                }
+                Some(command) = self.request_receiver.recv() => {
+                    self.handle_command(command).await;
+                }
+                _ = sleep_until(self.core.throttle.as_mut().map(|t| t.ready_at()).unwrap_or_else(Instant::now)),
+                    if self.core.throttle.as_ref().is_some_and(|t| !t.is_empty()) => {
+                    self.core.flush_throttled().await;
+                }
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.extension.handle_deadline(&mut self.core).await;
+                }
+                Some(message) = self.rithmic_reader.next() => {
+                    let stop = self.handle_rithmic_message(message).await.unwrap_or(true);
+
+                    if stop {
+                        break;
+                    }
+                }
+                else => { break }
+            }
+        }
+    }
+
+    async fn handle_rithmic_message(
+        &mut self,
+        message: Result<Message, Error>,
+    ) -> Result<bool, ()> {
+        let mut stop = false;
+
+        match message {
+            Ok(Message::Close(frame)) => {
+                event!(Level::INFO, "{}: received close frame: {:?}", E::NAME, frame);
+
+                stop = true;
+            }
+            Ok(Message::Binary(data)) => match self.core.rithmic_receiver_api.buf_to_message(data) {
+                Ok(response) => {
+                    let Some(response) = self.extension.handle_response(&mut self.core, response).await
+                    else {
+                        return Ok(false);
+                    };
+
+                    if matches!(
+                        response.message,
+                        RithmicMessage::Reject(_) | RithmicMessage::ForcedLogout(_)
+                    ) {
+                        self.core.handle_session_message(response);
+                    } else if let Some(error) = &response.error {
+                        // Updates with an error have nobody waiting for them
+                        if !response.is_update {
+                            self.core.request_handler.fail_request(&response.request_id, error);
+                        }
+                    } else if response.is_update {
+                        self.core.send_update(response);
+                    } else {
+                        self.core.request_handler.handle_response(response);
+                    }
+                }
+                Err(e) => {
+                    event!(Level::ERROR, "{}: failed to decode message: {:?}", E::NAME, e);
+                }
+            },
+            Err(Error::ConnectionClosed) => {
+                event!(Level::INFO, "{}: connection closed", E::NAME);
+
+                stop = true;
+            }
+            _ => {
+                event!(Level::WARN, "{}: unhandled message {:?}", E::NAME, message);
+            }
+        }
+
+        if stop {
+            self.core.handle_connection_closed();
+        }
+
+        Ok(stop)
+    }
+
+    async fn handle_command(&mut self, command: PlantCommand<E::Command>) {
+        match command {
+            PlantCommand::Close => {
+                self.core.send(Message::Close(None)).await;
+            }
+            PlantCommand::Login { response_sender } => {
+                let (login_buf, id) = self.core.rithmic_sender_api.request_login(
+                    &self.core.config.system_name,
+                    E::INFRA_TYPE,
+                    &self.core.config.user,
+                    &self.core.config.password,
+                );
+
+                event!(Level::INFO, "{}: sending login request {}", E::NAME, id);
+
+                self.core.request_handler.register_request(RithmicRequest {
+                    request_id: id,
+                    responder: response_sender,
+                });
+
+                self.core.send(Message::Binary(login_buf)).await;
+            }
+            PlantCommand::Logout { response_sender } => {
+                let (logout_buf, id) = self.core.rithmic_sender_api.request_logout();
+
+                self.core.request_handler.register_request(RithmicRequest {
+                    request_id: id,
+                    responder: response_sender,
+                });
+
+                self.core.send(Message::Binary(logout_buf)).await;
+            }
+            PlantCommand::SendHeartbeat {} => {
+                let (heartbeat_buf, _id) = self.core.rithmic_sender_api.request_heartbeat();
+
+                self.core.send(Message::Binary(heartbeat_buf)).await;
+            }
+            PlantCommand::SetLogin => {
+                self.core.logged_in = true;
+                self.core.send_session_event(RithmicSessionEvent::LoggedIn);
+            }
+            PlantCommand::ThrottleStats { response_sender } => {
+                let _ = response_sender.send(self.core.throttle.as_ref().map(|t| t.stats()));
+            }
+            PlantCommand::Plant(command) => {
+                self.extension.handle_command(&mut self.core, command).await;
+            }
+        }
+    }
+}
+
+/// Talks to a plant task, the plant specific requests are implemented on
+/// `RithmicPlantHandle<XPlantCommand>`
+pub struct RithmicPlantHandle<C> {
+    name: &'static str,
+    sender: mpsc::Sender<PlantCommand<C>>,
+    // Used for cloning and collecting snapshots
+    subscription_sender: Sender<RithmicResponse>,
+    pub subscription_receiver: broadcast::Receiver<RithmicResponse>,
+}
+
+impl<C: Send + 'static> RithmicPlantHandle<C> {
+    pub async fn login(&self) -> Result<RithmicResponse, String> {
+        event!(Level::INFO, "{}: logging in", self.name);
+
+        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+
+        self.send(PlantCommand::Login {
+            response_sender: tx,
+        })
+        .await;
+
+        let response = self.receive(rx).await?.remove(0);
+
+        match response.error.clone() {
+            None => {
+                self.send(PlantCommand::SetLogin).await;
+
+                event!(Level::INFO, "{}: logged in", self.name);
+
+                Ok(response)
+            }
+            Some(error) => {
+                event!(Level::ERROR, "{}: login failed {:?}", self.name, error);
+
+                Err(error)
+            }
+        }
+    }
+
+    pub async fn disconnect(&self) -> Result<RithmicResponse, String> {
+        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+
+        self.send(PlantCommand::Logout {
+            response_sender: tx,
+        })
+        .await;
+
+        let mut r = self.receive(rx).await?;
+        self.send(PlantCommand::Close).await;
+
+        Ok(r.remove(0))
+    }
+
+    /// Queueing statistics, None when the plant isn't throttled
+    pub async fn throttle_stats(&self) -> Option<RithmicThrottleStats> {
+        let (tx, rx) = oneshot::channel::<Option<RithmicThrottleStats>>();
+
+        self.send(PlantCommand::ThrottleStats {
+            response_sender: tx,
+        })
+        .await;
+
+        rx.await.ok().flatten()
+    }
+
+    /// Sends the command built by `command` and waits for the responses of its request
+    pub async fn request(
+        &self,
+        command: impl FnOnce(oneshot::Sender<Result<Vec<RithmicResponse>, String>>) -> C,
+    ) -> Result<Vec<RithmicResponse>, String> {
+        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+
+        self.send(PlantCommand::Plant(command(tx))).await;
+
+        self.receive(rx).await
+    }
+
+    /// Sends a command without waiting for anything
+    pub async fn send_command(&self, command: C) {
+        self.send(PlantCommand::Plant(command)).await;
+    }
+
+    /// New receiver of the plant updates, sent from now on
+    pub fn resubscribe(&self) -> broadcast::Receiver<RithmicResponse> {
+        self.subscription_sender.subscribe()
+    }
+
+    async fn send(&self, command: PlantCommand<C>) {
+        let _ = self.sender.send(command).await;
+    }
+
+    async fn receive(
+        &self,
+        rx: oneshot::Receiver<Result<Vec<RithmicResponse>, String>>,
+    ) -> Result<Vec<RithmicResponse>, String> {
+        rx.await
+            .map_err(|_| format!("{}: connection closed", self.name))?
+    }
+}
+
+#[cfg(test)]
+impl<C> RithmicPlantHandle<C> {
+    /// Handle whose commands are read from the returned receiver instead of a plant task
+    pub(crate) fn detached(name: &'static str) -> (Self, mpsc::Receiver<PlantCommand<C>>) {
+        let (sender, receiver) = mpsc::channel(32);
+        let (subscription_sender, subscription_receiver) = broadcast::channel(1024);
+
+        let handle = RithmicPlantHandle {
+            name,
+            sender,
+            subscription_sender,
+            subscription_receiver,
+        };
+
+        (handle, receiver)
+    }
+}
+
+impl<C> Clone for RithmicPlantHandle<C> {
+    fn clone(&self) -> Self {
+        RithmicPlantHandle {
+            name: self.name,
+            sender: self.sender.clone(),
+            subscription_sender: self.subscription_sender.clone(),
+            subscription_receiver: self.subscription_sender.subscribe(),
+        }
+    }
+}
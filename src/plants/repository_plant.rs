@@ -0,0 +1,204 @@
+use async_trait::async_trait;
+
+use crate::{
+    api::{
+        receiver_api::RithmicResponse,
+        rithmic_command_types::RithmicMarketDataUsageCapacity,
+    },
+    plants::plant::{PlantActorCore, PlantExtension, RithmicPlant, RithmicPlantHandle},
+    rti::{
+        ResponseListAcceptedAgreements, ResponseListUnacceptedAgreements, ResponseShowAgreement,
+        messages::RithmicMessage,
+        request_login::SysInfraType,
+    },
+};
+
+use tokio::sync::oneshot;
+
+pub enum RepositoryPlantCommand {
+    ListUnacceptedAgreements {
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ListAcceptedAgreements {
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    ShowAgreement {
+        agreement_id: String,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    AcceptAgreement {
+        agreement_id: String,
+        market_data_usage_capacity: Option<RithmicMarketDataUsageCapacity>,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    SetMarketDataSelfCertStatus {
+        agreement_id: String,
+        market_data_usage_capacity: RithmicMarketDataUsageCapacity,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+}
+
+/// Exchange agreements and market data self-certification.
+///
+/// Market data is only available once the exchange agreements of the user are accepted.
+pub type RithmicRepositoryPlant = RithmicPlant<RepositoryPlant>;
+
+pub type RithmicRepositoryPlantHandle = RithmicPlantHandle<RepositoryPlantCommand>;
+
+#[derive(Debug, Default)]
+pub struct RepositoryPlant;
+
+#[async_trait]
+impl PlantExtension for RepositoryPlant {
+    type Command = RepositoryPlantCommand;
+
+    const NAME: &'static str = "repository_plant";
+    const INFRA_TYPE: SysInfraType = SysInfraType::RepositoryPlant;
+
+    async fn handle_command(&mut self, core: &mut PlantActorCore, command: RepositoryPlantCommand) {
+        match command {
+            RepositoryPlantCommand::ListUnacceptedAgreements { response_sender } => {
+                let request = core.rithmic_sender_api.request_list_unaccepted_agreements();
+
+                core.send_request(request, response_sender, false).await;
+            }
+            RepositoryPlantCommand::ListAcceptedAgreements { response_sender } => {
+                let request = core.rithmic_sender_api.request_list_accepted_agreements();
+
+                core.send_request(request, response_sender, false).await;
+            }
+            RepositoryPlantCommand::ShowAgreement {
+                agreement_id,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_show_agreement(&agreement_id);
+
+                core.send_request(request, response_sender, false).await;
+            }
+            RepositoryPlantCommand::AcceptAgreement {
+                agreement_id,
+                market_data_usage_capacity,
+                response_sender,
+            } => {
+                let request = core
+                    .rithmic_sender_api
+                    .request_accept_agreement(&agreement_id, market_data_usage_capacity);
+
+                core.send_request(request, response_sender, false).await;
+            }
+            RepositoryPlantCommand::SetMarketDataSelfCertStatus {
+                agreement_id,
+                market_data_usage_capacity,
+                response_sender,
+            } => {
+                let request = core
+                    .rithmic_sender_api
+                    .request_set_rithmic_mrkt_data_self_cert_status(
+                        &agreement_id,
+                        market_data_usage_capacity,
+                    );
+
+                core.send_request(request, response_sender, false).await;
+            }
+        }
+    }
+}
+
+impl RithmicPlantHandle<RepositoryPlantCommand> {
+    /// Agreements the user still has to accept
+    pub async fn list_unaccepted_agreements(
+        &self,
+    ) -> Result<Vec<ResponseListUnacceptedAgreements>, String> {
+        let agreements = self
+            .request(|response_sender| RepositoryPlantCommand::ListUnacceptedAgreements {
+                response_sender,
+            })
+            .await?
+            .into_iter()
+            .filter_map(|response| match response.message {
+                RithmicMessage::ResponseListUnacceptedAgreements(resp)
+                    if resp.agreement_id.is_some() =>
+                {
+                    Some(resp)
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(agreements)
+    }
+
+    pub async fn list_accepted_agreements(
+        &self,
+    ) -> Result<Vec<ResponseListAcceptedAgreements>, String> {
+        let agreements = self
+            .request(|response_sender| RepositoryPlantCommand::ListAcceptedAgreements {
+                response_sender,
+            })
+            .await?
+            .into_iter()
+            .filter_map(|response| match response.message {
+                RithmicMessage::ResponseListAcceptedAgreements(resp)
+                    if resp.agreement_id.is_some() =>
+                {
+                    Some(resp)
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(agreements)
+    }
+
+    /// Text of an agreement, as plain text in `agreement` and as html in `agreement_html`
+    pub async fn show_agreement(&self, agreement_id: &str) -> Result<ResponseShowAgreement, String> {
+        self.request(|response_sender| RepositoryPlantCommand::ShowAgreement {
+            agreement_id: agreement_id.to_string(),
+            response_sender,
+        })
+        .await?
+        .into_iter()
+        .find_map(|response| match response.message {
+            RithmicMessage::ResponseShowAgreement(resp) if resp.agreement_id.is_some() => {
+                Some(resp)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| format!("repository_plant: unknown agreement {}", agreement_id))
+    }
+
+    /// Accepts an agreement, `market_data_usage_capacity` is required by the market data
+    /// agreements
+    pub async fn accept_agreement(
+        &self,
+        agreement_id: &str,
+        market_data_usage_capacity: Option<RithmicMarketDataUsageCapacity>,
+    ) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| RepositoryPlantCommand::AcceptAgreement {
+                agreement_id: agreement_id.to_string(),
+                market_data_usage_capacity,
+                response_sender,
+            })
+            .await?;
+
+        Ok(r.remove(0))
+    }
+
+    /// Submits the market data self-certification of an accepted agreement
+    pub async fn set_market_data_self_cert_status(
+        &self,
+        agreement_id: &str,
+        market_data_usage_capacity: RithmicMarketDataUsageCapacity,
+    ) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| RepositoryPlantCommand::SetMarketDataSelfCertStatus {
+                agreement_id: agreement_id.to_string(),
+                market_data_usage_capacity,
+                response_sender,
+            })
+            .await?;
+
+        Ok(r.remove(0))
+    }
+}
@@ -1,8 +1,7 @@
-use std::env;
+use std::time::Duration;
+
 use anyhow::anyhow;
-use async_trait::async_trait;
-use base64::Engine;
-use base64::prelude::BASE64_STANDARD;
+use bytes::Bytes;
 use tracing::{event, Level};
 
 use crate::{
@@ -12,55 +11,62 @@ use crate::{
         sender_api::RithmicSenderApi,
         DEFAULT_RTI_WS_URL,
     },
-    request_handler::{RithmicRequest, RithmicRequestHandler},
-    ws::{get_heartbeat_interval, PlantActor, RithmicStream, connect},
+    proxy::RithmicProxyConfig,
+    tls::RithmicTlsConfig,
+    ws::connect,
 };
 
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt,
+use futures_util::{future::join_all, SinkExt, StreamExt};
+
+use tokio::{
+    net::TcpStream,
+    time::{timeout, Instant},
 };
 
 use tokio_tungstenite::{
-    client_async,
-    connect_async,
-    tungstenite::{Error, Message},
-    Connector,
+    tungstenite::Message,
+    MaybeTlsStream,
     WebSocketStream,
-    MaybeTlsStream
 };
 
-use tokio::{
-    net::TcpStream,
-    sync::{broadcast::Sender, oneshot},
-    time::Interval,
-};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tungstenite::client::IntoClientRequest;
-use tungstenite::handshake::client::generate_key;
-use tungstenite::handshake::client::Request;
-use tungstenite::http;
-use tungstenite::http::header::{CONNECTION, UPGRADE};
-use tungstenite::http::Uri;
 use crate::rti::{
     ResponseRithmicSystemGatewayInfo, ResponseRithmicSystemInfo,
     messages::RithmicMessage
 };
 
-pub enum SharedPlantCommand {
-    RithmicSystemInfo {},
-    RithmicSystemGatewayInfo {
-        system_name: String
-    },
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Round trip times measured against one gateway
+#[derive(Clone, Debug)]
+pub struct RithmicGatewayLatency {
+    pub gateway_name: String,
+    pub url: String,
+    /// Time to open the websocket, including the TLS handshake
+    pub handshake: Duration,
+    /// Time between a heartbeat and the gateway reply
+    pub heartbeat: Duration,
 }
 
+/// System and gateway discovery.
+///
+/// Both requests go through a single connection to the bootstrap url, opened on the first
+/// request and kept until `close` is called.
 pub struct RithmicSharedPlant {
     rithmic_sender_api: RithmicSenderApi,
     rithmic_receiver_api: RithmicReceiverApi,
+    proxy: Option<RithmicProxyConfig>,
+    tls: Option<RithmicTlsConfig>,
+    url: String,
+    ws_stream: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
 }
 
 impl RithmicSharedPlant {
     pub fn new() -> RithmicSharedPlant {
+        RithmicSharedPlant::with_url(DEFAULT_RTI_WS_URL)
+    }
+
+    /// Discovery through another bootstrap url, e.g. a test environment or a mock server
+    pub fn with_url(url: &str) -> RithmicSharedPlant {
         let config = RithmicConnectionInfo::default();
         let rithmic_sender_api = RithmicSenderApi::new(&config);
         let rithmic_receiver_api = RithmicReceiverApi {
@@ -69,82 +75,221 @@ impl RithmicSharedPlant {
 
         RithmicSharedPlant {
             rithmic_sender_api,
-            rithmic_receiver_api
+            rithmic_receiver_api,
+            proxy: None,
+            tls: None,
+            url: url.to_string(),
+            ws_stream: None,
         }
     }
 
+    /// Connects through `proxy` instead of the proxy set in the environment
+    pub fn with_proxy(mut self, proxy: Option<RithmicProxyConfig>) -> RithmicSharedPlant {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Connects with `tls` instead of the system root certificates
+    pub fn with_tls(mut self, tls: Option<RithmicTlsConfig>) -> RithmicSharedPlant {
+        self.tls = tls;
+        self
+    }
+
     pub async fn rithmic_system_info(&mut self) -> Result<ResponseRithmicSystemInfo, anyhow::Error> {
-        let ws_stream = connect(DEFAULT_RTI_WS_URL).await.unwrap();
-        let (rithmic_sender, mut rithmic_reader) = ws_stream.split();
-
-        let command = SharedPlantCommand::RithmicSystemInfo {};
-        self.handle_command(rithmic_sender, command).await;
-        if let Some(message) = rithmic_reader.next().await {
-            if let Ok(Message::Binary(data)) = message {
-                if let RithmicMessage::ResponseRithmicSystemInfo(msg) = self.rithmic_receiver_api.buf_to_message(data).unwrap().message {
-                    Ok(msg)
-                } else {
-                    Err(anyhow!("message is not a rithmic system info"))
-                }
-            } else {
-                Err(anyhow!("rithmic message error"))
-            }
-        } else {
-            Err(anyhow!("rithmic message error"))
+        let (request_buf, id) = self.rithmic_sender_api.request_rithmic_system_info();
+
+        event!(Level::INFO, "shared_plant: sending system info request {}", id);
+
+        match self.request(request_buf, &id).await?.message {
+            RithmicMessage::ResponseRithmicSystemInfo(msg) => Ok(msg),
+            _ => Err(anyhow!("message is not a rithmic system info")),
         }
     }
 
     pub async fn rithmic_system_gateway_info(&mut self, system_name: String
     ) -> Result<ResponseRithmicSystemGatewayInfo, anyhow::Error> {
-        let ws_stream = connect(DEFAULT_RTI_WS_URL).await.unwrap();
-        let (rithmic_sender, mut rithmic_reader) = ws_stream.split();
+        let (request_buf, id) = self.rithmic_sender_api.request_rithmic_system_gateway_info(
+            system_name
+        );
 
-        let command = SharedPlantCommand::RithmicSystemGatewayInfo {
-            system_name,
-        };
-        self.handle_command(rithmic_sender, command).await;
-        if let Some(message) = rithmic_reader.next().await {
-            if let Ok(Message::Binary(data)) = message {
-                if let RithmicMessage::ResponseRithmicSystemGatewayInfo(msg) = self.rithmic_receiver_api.buf_to_message(data).unwrap().message {
-                    Ok(msg)
-                } else {
-                    Err(anyhow!("message is not a rithmic system gateway info"))
+        event!(Level::INFO, "shared_plant: sending system gateway info request {}", id);
+
+        match self.request(request_buf, &id).await?.message {
+            RithmicMessage::ResponseRithmicSystemGatewayInfo(msg) => Ok(msg),
+            _ => Err(anyhow!("message is not a rithmic system gateway info")),
+        }
+    }
+
+    /// Gateways of `system_name` that answered a probe, fastest heartbeat first
+    pub async fn ranked_gateways(
+        &mut self,
+        system_name: &str,
+    ) -> Result<Vec<RithmicGatewayLatency>, anyhow::Error> {
+        let gateways = self.rithmic_system_gateway_info(system_name.to_string()).await?;
+
+        let probes = gateways
+            .gateway_name
+            .iter()
+            .zip(gateways.gateway_uri.iter())
+            .map(|(name, url)| probe_gateway(name, url, self.proxy.as_ref(), self.tls.as_ref()));
+
+        let mut latencies: Vec<RithmicGatewayLatency> = join_all(probes)
+            .await
+            .into_iter()
+            .filter_map(|probe| match probe {
+                Ok(latency) => Some(latency),
+                Err(e) => {
+                    event!(Level::WARN, "shared_plant: gateway probe failed: {}", e);
+                    None
                 }
-            } else {
-                Err(anyhow!("rithmic message error"))
-            }
-        } else {
-            Err(anyhow!("rithmic message error"))
+            })
+            .collect();
+
+        latencies.sort_by_key(|l| (l.heartbeat, l.handshake));
+
+        Ok(latencies)
+    }
+
+    /// Closes the connection, the next request opens a new one
+    pub async fn close(&mut self) -> Result<(), anyhow::Error> {
+        if let Some(mut ws_stream) = self.ws_stream.take() {
+            ws_stream.close(None).await?;
+
+            // Wait for the server to acknowledge the close
+            while let Ok(Some(Ok(_))) = timeout(RESPONSE_TIMEOUT, ws_stream.next()).await {}
+
+            event!(Level::INFO, "shared_plant: connection closed");
         }
+
+        Ok(())
     }
 
-    async fn handle_command(
+    async fn request(
         &mut self,
-        mut rithmic_sender: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message,>,
-        command: SharedPlantCommand) {
-        match command {
-            SharedPlantCommand::RithmicSystemInfo {} => {
-                let (request_buf, id) = self.rithmic_sender_api.request_rithmic_system_info();
-
-                event!(Level::INFO, "shared_plant: sending system info request {}", id);
-
-                rithmic_sender
-                    .send(Message::Binary(request_buf))
-                    .await
-                    .unwrap();
+        request_buf: Bytes,
+        request_id: &str,
+    ) -> Result<RithmicResponse, anyhow::Error> {
+        if self.ws_stream.is_none() {
+            self.ws_stream = Some(connect(&self.url, self.proxy.as_ref(), self.tls.as_ref()).await?);
+        }
+
+        let ws_stream = self.ws_stream.as_mut().unwrap();
+
+        if let Err(e) = ws_stream.send(Message::Binary(request_buf)).await {
+            self.ws_stream = None;
+
+            return Err(e.into());
+        }
+
+        loop {
+            let message = match timeout(RESPONSE_TIMEOUT, ws_stream.next()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    self.ws_stream = None;
+
+                    return Err(anyhow!("connection closed by server"));
+                }
+                Err(_) => {
+                    // A late reply would be taken for the answer to the next request
+                    self.ws_stream = None;
+
+                    return Err(anyhow!("no response within {:?}", RESPONSE_TIMEOUT));
+                }
+            };
+
+            match message {
+                Ok(Message::Binary(data)) => {
+                    let response = self
+                        .rithmic_receiver_api
+                        .buf_to_message(data)
+                        .map_err(|e| anyhow!(e))?;
+
+                    if response.request_id != request_id {
+                        event!(
+                            Level::DEBUG,
+                            "shared_plant: ignoring reply to request {}",
+                            response.request_id
+                        );
+
+                        continue;
+                    }
+
+                    return match response.error {
+                        Some(error) => Err(anyhow!(error)),
+                        None => Ok(response),
+                    };
+                }
+                Ok(Message::Close(frame)) => {
+                    self.ws_stream = None;
+
+                    return Err(anyhow!("connection closed by server: {:?}", frame));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.ws_stream = None;
+
+                    return Err(e.into());
+                }
             }
-            SharedPlantCommand::RithmicSystemGatewayInfo { system_name } => {
-                let (request_buf, id) = self.rithmic_sender_api.request_rithmic_system_gateway_info(
-                    system_name
-                );
+        }
+    }
+}
+
+/// Connects to a gateway and times the websocket handshake and a heartbeat round trip
+pub async fn probe_gateway(
+    gateway_name: &str,
+    url: &str,
+    proxy: Option<&RithmicProxyConfig>,
+    tls: Option<&RithmicTlsConfig>,
+) -> Result<RithmicGatewayLatency, anyhow::Error> {
+    let start = Instant::now();
+
+    let mut ws_stream = timeout(RESPONSE_TIMEOUT, connect(url, proxy, tls))
+        .await
+        .map_err(|_| anyhow!("{}: no handshake within {:?}", gateway_name, RESPONSE_TIMEOUT))??;
+
+    let handshake = start.elapsed();
 
-                event!(Level::INFO, "shared_plant: sending system gateway info request {}", id);
+    let mut rithmic_sender_api = RithmicSenderApi::new(&RithmicConnectionInfo::default());
+    let (heartbeat_buf, _) = rithmic_sender_api.request_heartbeat();
 
-                rithmic_sender
-                    .send(Message::Binary(request_buf))
-                    .await
-                    .unwrap();
+    let start = Instant::now();
+
+    ws_stream.send(Message::Binary(heartbeat_buf)).await?;
+
+    // Any reply counts, the gateway may answer a heartbeat sent before login with a reject
+    let heartbeat = loop {
+        match timeout(RESPONSE_TIMEOUT, ws_stream.next()).await {
+            Ok(Some(Ok(Message::Binary(_)))) => break start.elapsed(),
+            Ok(Some(Ok(_))) => {}
+            Ok(Some(Err(e))) => return Err(e.into()),
+            Ok(None) => return Err(anyhow!("{}: connection closed by server", gateway_name)),
+            Err(_) => {
+                return Err(anyhow!("{}: no heartbeat reply within {:?}", gateway_name, RESPONSE_TIMEOUT));
             }
         }
+    };
+
+    let _ = ws_stream.close(None).await;
+
+    event!(
+        Level::INFO,
+        "shared_plant: {} handshake {:?} heartbeat {:?}",
+        gateway_name,
+        handshake,
+        heartbeat
+    );
+
+    Ok(RithmicGatewayLatency {
+        gateway_name: gateway_name.to_string(),
+        url: url.to_string(),
+        handshake,
+        heartbeat,
+    })
+}
+
+impl Default for RithmicSharedPlant {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
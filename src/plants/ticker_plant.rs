@@ -1,48 +1,50 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
 use async_trait::async_trait;
-use tracing::{event, Level};
 
 use crate::{
     api::{
         RithmicConnectionInfo,
-        receiver_api::{RithmicReceiverApi, RithmicResponse},
-        sender_api::RithmicSenderApi,
+        receiver_api::RithmicResponse,
+        rithmic_instrument_types::{RithmicInstrument, RithmicOptionChain},
+        rithmic_tick_size_types::{RithmicRoundDirection, RithmicTickSizeTable},
     },
-    request_handler::{RithmicRequest, RithmicRequestHandler},
+    plants::plant::{PlantActorCore, PlantExtension, RithmicPlant, RithmicPlantHandle},
     rti::{
+        ResponseGetInstrumentByUnderlying,
+        messages::RithmicMessage,
         request_login::SysInfraType,
         request_market_data_update::{Request, UpdateBits},
+        request_market_data_update_by_underlying,
         request_search_symbols::InstrumentType,
     },
-    ws::{get_heartbeat_interval, PlantActor, RithmicStream, connect},
+    throttle::RithmicThrottleConfig,
 };
 
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt,
+use chrono::Utc;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::sleep,
 };
+use tracing::{event, Level};
 
-use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{Error, Message},
-    WebSocketStream,
-    MaybeTlsStream
-};
+/// Time the instruments looked up together are gathered for before the cache file is written
+const INSTRUMENT_CACHE_SAVE_DELAY: Duration = Duration::from_secs(1);
 
-use tokio::{
-    net::TcpStream,
-    sync::{broadcast::Sender, oneshot},
-    time::Interval,
-};
+const INSTRUMENT_CACHE_MAX_AGE: chrono::Duration = chrono::Duration::days(1);
 
 pub enum TickerPlantCommand {
-    Close,
     GetInstrumentByUnderlying {
-        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
-    },
-    Login {
-        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
-    },
-    Logout {
+        underlying_symbol: String,
+        exchange: String,
+        expiration_date: Option<String>,
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
     ProductCodes {
@@ -60,8 +62,38 @@ pub enum TickerPlantCommand {
         exchange: Option<String>,
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
-    SendHeartbeat {},
-    SetLogin,
+    AuxilliaryReferenceData {
+        symbol: String,
+        exchange: String,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    CachedInstrument {
+        symbol: String,
+        exchange: String,
+        response_sender: oneshot::Sender<Option<Arc<RithmicInstrument>>>,
+    },
+    CacheInstrument {
+        instrument: Arc<RithmicInstrument>,
+    },
+    GiveTickSizeTypeTable {
+        tick_size_type: String,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
+    CachedTickSizeTable {
+        symbol: String,
+        exchange: String,
+        response_sender: oneshot::Sender<Option<Arc<RithmicTickSizeTable>>>,
+    },
+    CacheTickSizeTable {
+        symbol: String,
+        exchange: String,
+        table: Arc<RithmicTickSizeTable>,
+    },
+    VolumeAtPrice {
+        symbol: String,
+        exchange: String,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
     Subscribe {
         symbol: String,
         exchange: String,
@@ -69,280 +101,227 @@ pub enum TickerPlantCommand {
         request_type: Request,
         response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
     },
+    SubscribeByUnderlying {
+        underlying_symbol: String,
+        exchange: String,
+        expiration_date: Option<String>,
+        fields: Vec<request_market_data_update_by_underlying::UpdateBits>,
+        request_type: request_market_data_update_by_underlying::Request,
+        response_sender: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    },
 }
 
-pub struct RithmicTickerPlant {
-    pub connection_handle: tokio::task::JoinHandle<()>,
-    sender: tokio::sync::mpsc::Sender<TickerPlantCommand>,
-    subscription_sender: Sender<RithmicResponse>,
-}
+pub type RithmicTickerPlant = RithmicPlant<TickerPlant>;
 
-impl RithmicTickerPlant {
-    pub async fn new(conn_info: &RithmicConnectionInfo) -> RithmicTickerPlant {
-        let (req_tx, req_rx) = tokio::sync::mpsc::channel::<TickerPlantCommand>(32);
-        let (sub_tx, _sub_rx) = tokio::sync::broadcast::channel(1024);
+pub type RithmicTickerPlantHandle = RithmicPlantHandle<TickerPlantCommand>;
 
-        let mut ticker_plant = TickerPlant::new(req_rx, sub_tx.clone(), conn_info)
-            .await
-            .unwrap();
+#[derive(Debug, Default)]
+pub struct TickerPlant {
+    /// Reference data by symbol and exchange
+    instruments: HashMap<(String, String), Arc<RithmicInstrument>>,
+    /// File the instruments are saved to, as JSON
+    instrument_cache_file: Option<PathBuf>,
+    /// Task writing the cache file, started with the first instrument to save
+    instrument_saver: Option<mpsc::UnboundedSender<Arc<RithmicInstrument>>>,
+    /// Tick size tables by symbol and exchange
+    tick_size_tables: HashMap<(String, String), Arc<RithmicTickSizeTable>>,
+}
 
-        let connection_handle = tokio::spawn(async move {
-            ticker_plant.run().await;
-        });
+impl TickerPlant {
+    /// Keeps the instruments in `path` between runs, the ones older than a day are fetched again
+    pub fn with_instrument_cache_file(path: impl Into<PathBuf>) -> TickerPlant {
+        let path = path.into();
+        let oldest = Utc::now() - INSTRUMENT_CACHE_MAX_AGE;
+
+        let instruments = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str::<Vec<RithmicInstrument>>(&content)
+                .unwrap_or_else(|e| {
+                    event!(Level::WARN, "ticker_plant: invalid instrument cache {}: {}", path.display(), e);
+
+                    vec![]
+                }),
+            Err(_) => vec![],
+        };
 
-        RithmicTickerPlant {
-            connection_handle,
-            sender: req_tx,
-            subscription_sender: sub_tx,
+        TickerPlant {
+            instruments: instruments
+                .into_iter()
+                .filter(|instrument| instrument.fetched_at > oldest)
+                .map(|instrument| {
+                    ((instrument.symbol.clone(), instrument.exchange.clone()), Arc::new(instrument))
+                })
+                .collect(),
+            instrument_cache_file: Some(path),
+            instrument_saver: None,
+            tick_size_tables: HashMap::new(),
         }
     }
-}
 
-impl RithmicStream for RithmicTickerPlant {
-    type Handle = RithmicTickerPlantHandle;
+    fn save_instrument(&mut self, instrument: Arc<RithmicInstrument>) {
+        let Some(path) = &self.instrument_cache_file else {
+            return;
+        };
+
+        let saver = match &self.instrument_saver {
+            Some(saver) if !saver.is_closed() => saver,
+            _ => {
+                let (tx, rx) = mpsc::unbounded_channel();
 
-    fn get_handle(&self) -> RithmicTickerPlantHandle {
-        RithmicTickerPlantHandle {
-            sender: self.sender.clone(),
-            subscription_sender: self.subscription_sender.clone(),
-            subscription_receiver: self.subscription_sender.subscribe(),
-        }
+                tokio::spawn(save_instruments(path.clone(), self.instruments.clone(), rx));
+
+                self.instrument_saver.insert(tx)
+            }
+        };
+
+        let _ = saver.send(instrument);
     }
 }
 
-#[derive(Debug)]
-pub struct TickerPlant {
-    config: RithmicConnectionInfo,
-    interval: Interval,
-    logged_in: bool,
-    request_handler: RithmicRequestHandler,
-    request_receiver: tokio::sync::mpsc::Receiver<TickerPlantCommand>,
-    rithmic_reader: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    rithmic_receiver_api: RithmicReceiverApi,
-    rithmic_sender: SplitSink<
-        WebSocketStream<MaybeTlsStream<TcpStream>>,
-        Message,
-    >,
-
-    rithmic_sender_api: RithmicSenderApi,
-    subscription_sender: Sender<RithmicResponse>,
-}
+/// Writes the instruments to `path` as they are cached, at most once per
+/// `INSTRUMENT_CACHE_SAVE_DELAY`
+async fn save_instruments(
+    path: PathBuf,
+    mut instruments: HashMap<(String, String), Arc<RithmicInstrument>>,
+    mut receiver: mpsc::UnboundedReceiver<Arc<RithmicInstrument>>,
+) {
+    let insert = |instruments: &mut HashMap<_, _>, instrument: Arc<RithmicInstrument>| {
+        instruments.insert((instrument.symbol.clone(), instrument.exchange.clone()), instrument);
+    };
 
-impl TickerPlant {
-    async fn new(
-        request_receiver: tokio::sync::mpsc::Receiver<TickerPlantCommand>,
-        subscription_sender: Sender<RithmicResponse>,
-        conn_info: &RithmicConnectionInfo,
-    ) -> Result<TickerPlant, ()> {
-        let config = conn_info.clone();
-
-        let ws_stream = connect(&config.url).await.unwrap();
-        let (rithmic_sender, rithmic_reader) = ws_stream.split();
-        let rithmic_sender_api = RithmicSenderApi::new(&config);
-        let rithmic_receiver_api = RithmicReceiverApi {
-            source: "ticker_plant".to_string(),
+    while let Some(instrument) = receiver.recv().await {
+        insert(&mut instruments, instrument);
+
+        sleep(INSTRUMENT_CACHE_SAVE_DELAY).await;
+
+        while let Ok(instrument) = receiver.try_recv() {
+            insert(&mut instruments, instrument);
+        }
+
+        let mut sorted: Vec<&RithmicInstrument> =
+            instruments.values().map(|instrument| instrument.as_ref()).collect();
+
+        sorted.sort_by(|a, b| (&a.symbol, &a.exchange).cmp(&(&b.symbol, &b.exchange)));
+
+        let result = match serde_json::to_vec_pretty(&sorted) {
+            Ok(content) => tokio::fs::write(&path, content).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
         };
 
-        let interval = get_heartbeat_interval();
-
-        Ok(TickerPlant {
-            config,
-            interval,
-            logged_in: false,
-            request_handler: RithmicRequestHandler::new(),
-            request_receiver,
-            rithmic_reader,
-            rithmic_receiver_api,
-            rithmic_sender_api,
-            rithmic_sender,
-            subscription_sender,
-        })
+        if let Err(e) = result {
+            event!(Level::WARN, "ticker_plant: failed to save instruments to {}: {}", path.display(), e);
+        }
     }
 }
 
 #[async_trait]
-impl PlantActor for TickerPlant {
+impl PlantExtension for TickerPlant {
     type Command = TickerPlantCommand;
 
-    /// Execute the ticker plant in its own thread
-    /// We will listen for messages from request_receiver and forward them to Rithmic
-    /// while also listening for messages from Rithmic and forwarding them to subscription_sender
-    /// or request handler
-    async fn run(&mut self) {
-        loop {
-            tokio::select! {
-                _ = self.interval.tick() => {
-                    if self.logged_in {
-                        self.handle_command(TickerPlantCommand::SendHeartbeat {}).await;
-                    }
-                }
-                Some(message) = self.request_receiver.recv() => {
-                    self.handle_command(message).await;
-                }
-                Some(message) = self.rithmic_reader.next() => {
-                    let stop = self.handle_rithmic_message(message).await.unwrap();
+    const NAME: &'static str = "ticker_plant";
+    const INFRA_TYPE: SysInfraType = SysInfraType::TickerPlant;
 
-                    if stop {
-                        break;
-                    }
-                }
-                else => { break }
-            }
-        }
+    fn throttle(config: &RithmicConnectionInfo) -> Option<&RithmicThrottleConfig> {
+        config.ticker_throttle.as_ref()
     }
 
-    async fn handle_rithmic_message(
-        &mut self,
-        message: Result<Message, Error>,
-    ) -> Result<bool, ()> {
-        let mut stop = false;
-
-        match message {
-            Ok(Message::Close(frame)) => {
-                event!(
-                    Level::INFO,
-                    "ticker_plant received close frame: {:?}",
-                    frame
+    async fn handle_command(&mut self, core: &mut PlantActorCore, command: TickerPlantCommand) {
+        match command {
+            TickerPlantCommand::GetInstrumentByUnderlying {
+                underlying_symbol,
+                exchange,
+                expiration_date,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_get_instrument_by_underlying(
+                    &underlying_symbol,
+                    &exchange,
+                    expiration_date,
                 );
 
-                stop = true;
+                core.send_request(request, response_sender, false).await;
             }
-            Ok(Message::Binary(data)) => {
-                let response = self.rithmic_receiver_api.buf_to_message(data).unwrap();
+            TickerPlantCommand::ProductCodes { exchange, response_sender } => {
+                let request = core.rithmic_sender_api.request_product_codes(exchange);
 
-                if response.is_update {
-                    self.subscription_sender.send(response).unwrap();
-                } else {
-                    self.request_handler.handle_response(response);
-                }
+                core.send_request(request, response_sender, false).await;
             }
-            Err(Error::ConnectionClosed) => {
-                event!(Level::INFO, "ticker_plant connection closed");
+            TickerPlantCommand::ReferenceData { symbol, exchange, response_sender } => {
+                let request = core.rithmic_sender_api.request_reference_data(symbol, exchange);
 
-                stop = true;
+                core.send_request(request, response_sender, false).await;
             }
-            _ => {
-                event!(
-                    Level::WARN,
-                    "ticker_plant received unknown message {:?}",
-                    message
+            TickerPlantCommand::SearchSymbols {
+                search_text,
+                instrument_type,
+                exact_search,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_search_symbols(
+                    search_text,
+                    instrument_type,
+                    exact_search,
                 );
-            }
-        }
 
-        Ok(stop)
-    }
+                core.send_request(request, response_sender, false).await;
+            }
+            TickerPlantCommand::AuxilliaryReferenceData {
+                symbol,
+                exchange,
+                response_sender,
+            } => {
+                let request = core
+                    .rithmic_sender_api
+                    .request_auxilliary_reference_data(&symbol, &exchange);
 
-    async fn handle_command(&mut self, command: TickerPlantCommand) {
-        match command {
-            TickerPlantCommand::Close => {
-                self.rithmic_sender
-                    .send(Message::Close(None))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
-            TickerPlantCommand::GetInstrumentByUnderlying { response_sender} => {
-                let (request_buf, id) = self.rithmic_sender_api.request_get_instrument_by_underlying();
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                let _ = self.rithmic_sender
-                    .send(Message::Binary(request_buf))
-                    .await
-                    .unwrap();
+            TickerPlantCommand::CachedInstrument {
+                symbol,
+                exchange,
+                response_sender,
+            } => {
+                let _ = response_sender.send(self.instruments.get(&(symbol, exchange)).cloned());
             }
-            TickerPlantCommand::Login { response_sender } => {
-                let (login_buf, id) = self.rithmic_sender_api.request_login(
-                    &self.config.system_name,
-                    SysInfraType::TickerPlant,
-                    &self.config.user,
-                    &self.config.password,
+            TickerPlantCommand::CacheInstrument { instrument } => {
+                self.instruments.insert(
+                    (instrument.symbol.clone(), instrument.exchange.clone()),
+                    instrument.clone(),
                 );
 
-                event!(Level::INFO, "ticker_plant: sending login request {}", id);
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(login_buf))
-                    .await
-                    .unwrap();
+                self.save_instrument(instrument);
             }
+            TickerPlantCommand::GiveTickSizeTypeTable {
+                tick_size_type,
+                response_sender,
+            } => {
+                let request = core
+                    .rithmic_sender_api
+                    .request_give_tick_size_type_table(&tick_size_type);
 
-            TickerPlantCommand::Logout { response_sender } => {
-                let (logout_buf, id) = self.rithmic_sender_api.request_logout();
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                self.rithmic_sender
-                    .send(Message::Binary(logout_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
-            TickerPlantCommand::ProductCodes { exchange , response_sender} => {
-                let (request_buf, id) = self.rithmic_sender_api.request_product_codes(exchange);
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                let _ = self.rithmic_sender
-                    .send(Message::Binary(request_buf))
-                    .await
-                    .unwrap();
-            }
-            TickerPlantCommand::ReferenceData { symbol, exchange , response_sender} => {
-                let (request_buf, id) = self.rithmic_sender_api.request_reference_data(
-                    symbol, exchange
-                );
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                let _ = self.rithmic_sender
-                    .send(Message::Binary(request_buf))
-                    .await
-                    .unwrap();
+            TickerPlantCommand::CachedTickSizeTable {
+                symbol,
+                exchange,
+                response_sender,
+            } => {
+                let _ = response_sender.send(self.tick_size_tables.get(&(symbol, exchange)).cloned());
             }
-            TickerPlantCommand::SearchSymbols { search_text , instrument_type, exact_search, response_sender} => {
-                let (request_buf, id) = self.rithmic_sender_api.request_search_symbols(
-                    search_text,
-                    instrument_type,
-                    exact_search
-                );
-
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
-
-                let _ = self.rithmic_sender
-                    .send(Message::Binary(request_buf))
-                    .await
-                    .unwrap();
+            TickerPlantCommand::CacheTickSizeTable {
+                symbol,
+                exchange,
+                table,
+            } => {
+                self.tick_size_tables.insert((symbol, exchange), table);
             }
-            TickerPlantCommand::SendHeartbeat {} => {
-                let (heartbeat_buf, _id) = self.rithmic_sender_api.request_heartbeat();
+            TickerPlantCommand::VolumeAtPrice {
+                symbol,
+                exchange,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_get_volume_at_price(&symbol, &exchange);
 
-                let _ = self
-                    .rithmic_sender
-                    .send(Message::Binary(heartbeat_buf))
-                    .await;
-            }
-            TickerPlantCommand::SetLogin => {
-                self.logged_in = true;
+                core.send_request(request, response_sender, false).await;
             }
             TickerPlantCommand::Subscribe {
                 symbol,
@@ -351,123 +330,164 @@ impl PlantActor for TickerPlant {
                 request_type,
                 response_sender,
             } => {
-                let (sub_buf, id) = self.rithmic_sender_api.request_market_data_update(
+                let request = core.rithmic_sender_api.request_market_data_update(
                     &symbol,
                     &exchange,
                     fields,
                     request_type,
                 );
 
-                self.request_handler.register_request(RithmicRequest {
-                    request_id: id,
-                    responder: response_sender,
-                });
+                core.send_request(request, response_sender, false).await;
+            }
+            TickerPlantCommand::SubscribeByUnderlying {
+                underlying_symbol,
+                exchange,
+                expiration_date,
+                fields,
+                request_type,
+                response_sender,
+            } => {
+                let request = core.rithmic_sender_api.request_market_data_update_by_underlying(
+                    &underlying_symbol,
+                    &exchange,
+                    expiration_date,
+                    fields,
+                    request_type,
+                );
 
-                self.rithmic_sender
-                    .send(Message::Binary(sub_buf))
-                    .await
-                    .unwrap();
+                core.send_request(request, response_sender, false).await;
             }
         }
     }
 }
 
-pub struct RithmicTickerPlantHandle {
-    sender: tokio::sync::mpsc::Sender<TickerPlantCommand>,
-    // Used for cloning
-    subscription_sender: tokio::sync::broadcast::Sender<RithmicResponse>,
-    pub subscription_receiver: tokio::sync::broadcast::Receiver<RithmicResponse>,
-}
-
-impl RithmicTickerPlantHandle {
-    pub async fn login(&self) -> Result<RithmicResponse, String> {
-        event!(Level::INFO, "ticker_plant: logging in");
-
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = TickerPlantCommand::Login {
-            response_sender: tx,
-        };
-
-        let _ = self.sender.send(command).await;
-        let response = rx.await.unwrap().unwrap().remove(0);
-
-        if response.error.is_none() {
-            let _ = self.sender.send(TickerPlantCommand::SetLogin).await;
-
-            event!(Level::INFO, "ticker_plant: logged in");
-
-            Ok(response)
-        } else {
-            event!(
-                Level::ERROR,
-                "ticker_plant: login failed {:?}",
-                response.error
-            );
+impl RithmicPlantHandle<TickerPlantCommand> {
+    /// Instruments of an underlying, e.g. the options on a future, of every expiration when
+    /// `expiration_date` is None. Dates are formatted as in the reference data.
+    pub async fn get_instrument_by_underlying(
+        &self,
+        underlying_symbol: &str,
+        exchange: &str,
+        expiration_date: Option<&str>,
+    ) -> Result<Vec<ResponseGetInstrumentByUnderlying>, String> {
+        let instruments = self
+            .request(|response_sender| TickerPlantCommand::GetInstrumentByUnderlying {
+                underlying_symbol: underlying_symbol.to_string(),
+                exchange: exchange.to_string(),
+                expiration_date: expiration_date.map(str::to_string),
+                response_sender,
+            })
+            .await?
+            .into_iter()
+            .filter_map(|response| match response.message {
+                RithmicMessage::ResponseGetInstrumentByUnderlying(resp) if resp.symbol.is_some() => {
+                    Some(resp)
+                }
+                _ => None,
+            })
+            .collect();
 
-            Err(response.error.unwrap())
-        }
+        Ok(instruments)
     }
 
-    pub async fn disconnect(&self) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = TickerPlantCommand::Logout {
-            response_sender: tx,
-        };
-
-        let _ = self.sender.send(command).await;
-        let mut r = rx.await.unwrap().unwrap();
-        let _ = self.sender.send(TickerPlantCommand::Close).await;
-        let response = r.remove(0);
-
-        self.subscription_sender.send(response.clone()).unwrap();
-
-        Ok(response)
+    /// Same as `get_instrument_by_underlying`, ordered by expiration and strike
+    pub async fn option_chain(
+        &self,
+        underlying_symbol: &str,
+        exchange: &str,
+        expiration_date: Option<&str>,
+    ) -> Result<RithmicOptionChain, String> {
+        let instruments = self
+            .get_instrument_by_underlying(underlying_symbol, exchange, expiration_date)
+            .await?
+            .iter()
+            .map(RithmicInstrument::from)
+            .collect();
+
+        Ok(RithmicOptionChain::new(underlying_symbol, exchange, instruments))
     }
 
-    pub async fn get_instrument_by_underlying(&self) -> Result<Vec<RithmicResponse>, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+    /// Subscribes to the market data of every instrument of an underlying with one request.
+    /// Updates come per instrument, as after `subscribe`.
+    pub async fn subscribe_by_underlying(
+        &self,
+        underlying_symbol: &str,
+        exchange: &str,
+        expiration_date: Option<&str>,
+        fields: Vec<request_market_data_update_by_underlying::UpdateBits>,
+    ) -> Result<RithmicResponse, String> {
+        self.market_data_by_underlying(
+            underlying_symbol,
+            exchange,
+            expiration_date,
+            fields,
+            request_market_data_update_by_underlying::Request::Subscribe,
+        )
+        .await
+    }
 
-        let command = TickerPlantCommand::GetInstrumentByUnderlying {
-            response_sender: tx,
-        };
+    pub async fn unsubscribe_by_underlying(
+        &self,
+        underlying_symbol: &str,
+        exchange: &str,
+        expiration_date: Option<&str>,
+        fields: Vec<request_market_data_update_by_underlying::UpdateBits>,
+    ) -> Result<RithmicResponse, String> {
+        self.market_data_by_underlying(
+            underlying_symbol,
+            exchange,
+            expiration_date,
+            fields,
+            request_market_data_update_by_underlying::Request::Unsubscribe,
+        )
+        .await
+    }
 
-        let _ = self.sender.send(command).await;
+    async fn market_data_by_underlying(
+        &self,
+        underlying_symbol: &str,
+        exchange: &str,
+        expiration_date: Option<&str>,
+        fields: Vec<request_market_data_update_by_underlying::UpdateBits>,
+        request_type: request_market_data_update_by_underlying::Request,
+    ) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| TickerPlantCommand::SubscribeByUnderlying {
+                underlying_symbol: underlying_symbol.to_string(),
+                exchange: exchange.to_string(),
+                expiration_date: expiration_date.map(str::to_string),
+                fields,
+                request_type,
+                response_sender,
+            })
+            .await?;
 
-        Ok(rx.await.unwrap()?)
+        Ok(r.remove(0))
     }
 
     pub async fn product_codes(&self,
                                 exchange: Option<String>
     ) -> Result<Vec<RithmicResponse>, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = TickerPlantCommand::ProductCodes {
+        self.request(|response_sender| TickerPlantCommand::ProductCodes {
             exchange,
-            response_sender: tx,
-        };
-
-        let _ = self.sender.send(command).await;
-
-        Ok(rx.await.unwrap()?)
+            response_sender,
+        })
+        .await
     }
 
     pub async fn reference_data(&self,
                                 symbol: Option<String>,
                                 exchange: Option<String>
     ) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = TickerPlantCommand::ReferenceData {
-            symbol,
-            exchange,
-            response_sender: tx,
-        };
-
-        let _ = self.sender.send(command).await;
+        let mut r = self
+            .request(|response_sender| TickerPlantCommand::ReferenceData {
+                symbol,
+                exchange,
+                response_sender,
+            })
+            .await?;
 
-        Ok(rx.await.unwrap()?.remove(0))
+        Ok(r.remove(0))
     }
 
     pub async fn search_symbols(&self,
@@ -475,48 +495,297 @@ impl RithmicTickerPlantHandle {
                                 instrument_type: Option<InstrumentType>,
                                 exact_search: Option<bool>
     ) -> Result<Vec<RithmicResponse>, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
-
-        let command = TickerPlantCommand::SearchSymbols {
+        self.request(|response_sender| TickerPlantCommand::SearchSymbols {
             search_text,
             instrument_type,
             exact_search,
+            response_sender,
+        })
+        .await
+    }
+
+    pub async fn subscribe(
+        &self,
+        symbol: &str,
+        exchange: &str,
+        fields: Vec<UpdateBits>
+    ) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| TickerPlantCommand::Subscribe {
+                symbol: symbol.to_string(),
+                exchange: exchange.to_string(),
+                fields,
+                request_type: Request::Subscribe,
+                response_sender,
+            })
+            .await?;
+
+        Ok(r.remove(0))
+    }
+
+    pub async fn auxilliary_reference_data(
+        &self,
+        symbol: &str,
+        exchange: &str,
+    ) -> Result<RithmicResponse, String> {
+        let mut r = self
+            .request(|response_sender| TickerPlantCommand::AuxilliaryReferenceData {
+                symbol: symbol.to_string(),
+                exchange: exchange.to_string(),
+                response_sender,
+            })
+            .await?;
+
+        Ok(r.remove(0))
+    }
+
+    /// Reference and auxiliary reference data of an instrument, fetched once per plant (or per
+    /// cache file) and then cached
+    pub async fn instrument(
+        &self,
+        symbol: &str,
+        exchange: &str,
+    ) -> Result<Arc<RithmicInstrument>, String> {
+        let (tx, rx) = oneshot::channel();
+
+        self.send_command(TickerPlantCommand::CachedInstrument {
+            symbol: symbol.to_string(),
+            exchange: exchange.to_string(),
             response_sender: tx,
+        })
+        .await;
+
+        if let Ok(Some(instrument)) = rx.await {
+            return Ok(instrument);
+        }
+
+        let response = self
+            .reference_data(Some(symbol.to_string()), Some(exchange.to_string()))
+            .await?;
+
+        let RithmicMessage::ResponseReferenceData(reference_data) = response.message else {
+            return Err(format!("ticker_plant: no reference data for {} {}", symbol, exchange));
+        };
+
+        // Not every instrument has auxiliary data, Rithmic answers those with an error
+        let auxilliary = match self.auxilliary_reference_data(symbol, exchange).await {
+            Ok(response) => match response.message {
+                RithmicMessage::ResponseAuxilliaryReferenceData(auxilliary) => Some(auxilliary),
+                _ => None,
+            },
+            Err(e) => {
+                event!(
+                    Level::WARN,
+                    "ticker_plant: no auxiliary reference data for {} {}: {}",
+                    symbol,
+                    exchange,
+                    e
+                );
+
+                None
+            }
         };
 
-        let _ = self.sender.send(command).await;
+        let instrument = Arc::new(RithmicInstrument::new(&reference_data, auxilliary.as_ref()));
+
+        self.send_command(TickerPlantCommand::CacheInstrument {
+            instrument: instrument.clone(),
+        })
+        .await;
 
-        Ok(rx.await.unwrap()?)
+        Ok(instrument)
     }
 
-    pub async fn subscribe(
+    /// Price bands of a tick size type, as given by the reference data of an instrument
+    pub async fn tick_size_type_table(
+        &self,
+        tick_size_type: &str,
+    ) -> Result<RithmicTickSizeTable, String> {
+        let rows: Vec<_> = self
+            .request(|response_sender| TickerPlantCommand::GiveTickSizeTypeTable {
+                tick_size_type: tick_size_type.to_string(),
+                response_sender,
+            })
+            .await?
+            .into_iter()
+            .filter_map(|response| match response.message {
+                RithmicMessage::ResponseGiveTickSizeTypeTable(row) if row.rp_code.is_empty() => {
+                    Some(row)
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(RithmicTickSizeTable::from_rows(tick_size_type, &rows))
+    }
+
+    /// Tick size table of an instrument, fetched once per plant and then cached
+    pub async fn tick_size_table(
         &self,
         symbol: &str,
         exchange: &str,
-        fields: Vec<UpdateBits>
-    ) -> Result<RithmicResponse, String> {
-        let (tx, rx) = oneshot::channel::<Result<Vec<RithmicResponse>, String>>();
+    ) -> Result<Arc<RithmicTickSizeTable>, String> {
+        let (tx, rx) = oneshot::channel();
 
-        let command = TickerPlantCommand::Subscribe {
+        self.send_command(TickerPlantCommand::CachedTickSizeTable {
             symbol: symbol.to_string(),
             exchange: exchange.to_string(),
-            fields,
-            request_type: Request::Subscribe,
             response_sender: tx,
+        })
+        .await;
+
+        if let Ok(Some(table)) = rx.await {
+            return Ok(table);
+        }
+
+        let instrument = self.instrument(symbol, exchange).await?;
+
+        let mut table = match instrument.tick_size_type.as_deref() {
+            Some(tick_size_type) => self.tick_size_type_table(tick_size_type).await?,
+            None => RithmicTickSizeTable::default(),
         };
 
-        let _ = self.sender.send(command).await;
+        if table.bands.is_empty() {
+            let tick_size = instrument
+                .tick_size
+                .filter(|tick_size| *tick_size > 0.0)
+                .ok_or_else(|| format!("ticker_plant: no tick size for {} {}", symbol, exchange))?;
+
+            table = RithmicTickSizeTable {
+                tick_size_type: table.tick_size_type,
+                ..RithmicTickSizeTable::fixed(tick_size)
+            };
+        }
+
+        let table = Arc::new(table);
 
-        Ok(rx.await.unwrap()?.remove(0))
+        self.send_command(TickerPlantCommand::CacheTickSizeTable {
+            symbol: symbol.to_string(),
+            exchange: exchange.to_string(),
+            table: table.clone(),
+        })
+        .await;
+
+        Ok(table)
     }
-}
 
-impl Clone for RithmicTickerPlantHandle {
-    fn clone(&self) -> Self {
-        RithmicTickerPlantHandle {
-            sender: self.sender.clone(),
-            subscription_sender: self.subscription_sender.clone(),
-            subscription_receiver: self.subscription_sender.subscribe(),
+    pub async fn round_to_tick(
+        &self,
+        symbol: &str,
+        exchange: &str,
+        price: f64,
+        direction: RithmicRoundDirection,
+    ) -> Result<f64, String> {
+        self.tick_size_table(symbol, exchange)
+            .await?
+            .round_to_tick(price, direction)
+            .ok_or_else(|| format!("ticker_plant: no tick size for {} at {}", symbol, price))
+    }
+
+    /// Signed number of ticks from `from` to `to`
+    pub async fn ticks_between(
+        &self,
+        symbol: &str,
+        exchange: &str,
+        from: f64,
+        to: f64,
+    ) -> Result<i64, String> {
+        self.tick_size_table(symbol, exchange)
+            .await?
+            .ticks_between(from, to)
+            .ok_or_else(|| format!("ticker_plant: no tick size for {} between {} and {}", symbol, from, to))
+    }
+
+    /// Checks an order price, or a bracket target or stop level, is on a tick of the instrument
+    pub async fn validate_price(&self, symbol: &str, exchange: &str, price: f64) -> Result<(), String> {
+        if self.tick_size_table(symbol, exchange).await?.is_valid_price(price) {
+            Ok(())
+        } else {
+            Err(format!("{} is not a valid price for {} {}", price, symbol, exchange))
         }
     }
+
+    /// Volume traded at each price during the current session
+    pub async fn volume_at_price(
+        &self,
+        symbol: &str,
+        exchange: &str,
+    ) -> Result<RithmicVolumeProfile, String> {
+        let responses = self
+            .request(|response_sender| TickerPlantCommand::VolumeAtPrice {
+                symbol: symbol.to_string(),
+                exchange: exchange.to_string(),
+                response_sender,
+            })
+            .await?;
+
+        let mut profile = RithmicVolumeProfile {
+            symbol: symbol.to_string(),
+            exchange: exchange.to_string(),
+            levels: BTreeMap::new(),
+        };
+
+        for response in responses {
+            if let RithmicMessage::ResponseGetVolumeAtPrice(resp) = response.message {
+                for (price, volume) in resp.trade_price.iter().zip(&resp.volume_at_price) {
+                    *profile.levels.entry(RithmicPrice(*price)).or_default() +=
+                        u64::try_from(*volume).unwrap_or_default();
+                }
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+/// Price usable as a map key, ordered with `f64::total_cmp`
+#[derive(Debug, Clone, Copy)]
+pub struct RithmicPrice(pub f64);
+
+impl PartialEq for RithmicPrice {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RithmicPrice {}
+
+impl PartialOrd for RithmicPrice {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RithmicPrice {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Session volume profile of a symbol
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RithmicVolumeProfile {
+    pub symbol: String,
+    pub exchange: String,
+    /// Volume by price, lowest price first
+    pub levels: BTreeMap<RithmicPrice, u64>,
+}
+
+impl RithmicVolumeProfile {
+    pub fn volume_at(&self, price: f64) -> u64 {
+        self.levels.get(&RithmicPrice(price)).copied().unwrap_or_default()
+    }
+
+    pub fn total_volume(&self) -> u64 {
+        self.levels.values().sum()
+    }
+
+    /// Price with the most volume, the lowest one on a tie
+    pub fn point_of_control(&self) -> Option<f64> {
+        self.levels
+            .iter()
+            .rev()
+            .max_by_key(|(_, volume)| **volume)
+            .map(|(price, _)| price.0)
+    }
 }
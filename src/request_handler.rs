@@ -36,17 +36,36 @@ impl RithmicRequestHandler {
             _ => {
                 if !response.multi_response {
                     if let Some(responder) = self.handle_map.remove(&response.request_id) {
-                        responder.send(Ok(vec![response])).unwrap();
+                        // Updates attached to the request come first
+                        let mut response_vec = self
+                            .response_vec_map
+                            .remove(&response.request_id)
+                            .unwrap_or_default();
+
+                        response_vec.push(response);
+                        respond(responder, Ok(response_vec));
                     } else {
                         event!(Level::ERROR, "No responder found for response: {:#?}", response);
                     }
                 } else {
                     // If response has more, we store it in a vector and wait for more messages
                     if response.has_more {
-                        self.response_vec_map
-                            .entry(response.request_id.clone())
-                            .or_default()
-                            .push(response);
+                        match self.handle_map.get(&response.request_id) {
+                            Some(responder) if responder.is_closed() => {
+                                // The caller is gone, drop what was gathered for it
+                                self.handle_map.remove(&response.request_id);
+                                self.response_vec_map.remove(&response.request_id);
+                            }
+                            Some(_) => {
+                                self.response_vec_map
+                                    .entry(response.request_id.clone())
+                                    .or_default()
+                                    .push(response);
+                            }
+                            None => {
+                                event!(Level::DEBUG, "No responder found for response: {:#?}", response);
+                            }
+                        }
                     } else if let Some(responder) = self.handle_map.remove(&response.request_id) {
                         let response_vec = match self.response_vec_map.remove(&response.request_id)
                         {
@@ -58,7 +77,7 @@ impl RithmicRequestHandler {
                                 vec![response]
                             }
                         };
-                        responder.send(Ok(response_vec)).unwrap();
+                        respond(responder, Ok(response_vec));
                     } else {
                         event!(Level::ERROR, "No responder found for response: {:#?}", response);
                     }
@@ -66,6 +85,43 @@ impl RithmicRequestHandler {
             }
         }
     }
+
+    /// Whether the request waits for its response, false once its caller dropped the receiver
+    pub fn is_pending(&self, request_id: &str) -> bool {
+        self.handle_map
+            .get(request_id)
+            .is_some_and(|responder| !responder.is_closed())
+    }
+
+    /// Completes a pending request with an error, e.g. when Rithmic rejected it
+    pub fn fail_request(&mut self, request_id: &str, reason: &str) {
+        self.response_vec_map.remove(request_id);
+
+        if let Some(responder) = self.handle_map.remove(request_id) {
+            respond(responder, Err(reason.to_string()));
+        } else {
+            event!(Level::WARN, "No responder found for failed request {}", request_id);
+        }
+    }
+
+    /// Completes every pending request with an error, used when the session is gone
+    pub fn fail_all(&mut self, reason: &str) {
+        self.response_vec_map.clear();
+
+        for (_, responder) in self.handle_map.drain() {
+            let _ = responder.send(Err(reason.to_string()));
+        }
+    }
+}
+
+/// Answers a request, the caller may have stopped waiting for it
+fn respond(
+    responder: oneshot::Sender<Result<Vec<RithmicResponse>, String>>,
+    result: Result<Vec<RithmicResponse>, String>,
+) {
+    if responder.send(result).is_err() {
+        event!(Level::DEBUG, "Request dropped before its response arrived");
+    }
 }
 
 impl Default for RithmicRequestHandler {
@@ -0,0 +1,818 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::{sync::broadcast, task::JoinHandle};
+use tracing::{event, Level};
+
+use crate::{
+    api::{
+        receiver_api::RithmicResponse,
+        rithmic_command_types::{
+            RithmicBracketOrder, RithmicCancelOrder, RithmicModifyOrder, RithmicOrder,
+        },
+    },
+    plants::order_plant::RithmicOrderPlantHandle,
+    rti::{
+        messages::RithmicMessage,
+        request_bracket_order, request_new_order,
+        rithmic_order_notification::{NotifyType, TransactionType},
+    },
+};
+
+/// Limits enforced locally before an order is sent to Rithmic. `None` disables a rule.
+#[derive(Debug, Clone, Default)]
+pub struct RithmicRiskConfig {
+    pub max_order_qty: Option<i32>,
+    /// Applies to every symbol without an entry in `max_position_by_symbol`
+    pub max_position: Option<i32>,
+    pub max_position_by_symbol: HashMap<String, i32>,
+    pub max_working_orders: Option<usize>,
+    /// Positive amount, new orders are rejected once the day PnL reaches `-max_daily_loss`
+    pub max_daily_loss: Option<f64>,
+    /// Maximum distance of an order price from the last trade, as a fraction (0.01 = 1%)
+    pub price_collar: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RithmicRiskRejection {
+    KillSwitch,
+    MaxOrderQty { qty: i32, max: i32 },
+    MaxPosition { symbol: String, position: i32, max: i32 },
+    MaxWorkingOrders { working: usize, max: usize },
+    DailyLossLimit { day_pnl: f64, max_loss: f64 },
+    PriceCollar { price: f64, last_trade: f64, max_deviation: f64 },
+    NoReferencePrice { symbol: String },
+    /// Modify of an order the gate has no quantity or side for
+    UnknownOrder { id: String },
+}
+
+impl fmt::Display for RithmicRiskRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RithmicRiskRejection::KillSwitch => write!(f, "kill switch is engaged"),
+            RithmicRiskRejection::MaxOrderQty { qty, max } => {
+                write!(f, "order quantity {} exceeds max {}", qty, max)
+            }
+            RithmicRiskRejection::MaxPosition { symbol, position, max } => {
+                write!(f, "{} position would reach {}, max {}", symbol, position, max)
+            }
+            RithmicRiskRejection::MaxWorkingOrders { working, max } => {
+                write!(f, "{} working orders, max {}", working, max)
+            }
+            RithmicRiskRejection::DailyLossLimit { day_pnl, max_loss } => {
+                write!(f, "day pnl {} breached daily loss limit {}", day_pnl, max_loss)
+            }
+            RithmicRiskRejection::PriceCollar {
+                price,
+                last_trade,
+                max_deviation,
+            } => write!(
+                f,
+                "price {} is more than {}% away from last trade {}",
+                price,
+                max_deviation * 100.0,
+                last_trade
+            ),
+            RithmicRiskRejection::NoReferencePrice { symbol } => {
+                write!(f, "no last trade for {} to check the price collar", symbol)
+            }
+            RithmicRiskRejection::UnknownOrder { id } => {
+                write!(f, "order {} is unknown, its quantity can't be checked", id)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RithmicRiskError {
+    /// Rejected locally, nothing was sent
+    Rejected(RithmicRiskRejection),
+    /// Error returned by the order plant
+    Order(String),
+}
+
+impl fmt::Display for RithmicRiskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RithmicRiskError::Rejected(reason) => write!(f, "order rejected by risk gate: {}", reason),
+            RithmicRiskError::Order(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RithmicRiskError {}
+
+#[derive(Debug, Clone, Default)]
+struct RithmicPosition {
+    net_qty: i32,
+    working_buy_qty: i32,
+    working_sell_qty: i32,
+    /// Quantity of the orders being sent
+    pending_buy_qty: i32,
+    pending_sell_qty: i32,
+    /// Quantity of the orders sent since the last position update
+    placed_buy_qty: i32,
+    placed_sell_qty: i32,
+}
+
+impl RithmicPosition {
+    fn buy_qty(&self) -> i32 {
+        self.working_buy_qty + self.pending_buy_qty + self.placed_buy_qty
+    }
+
+    fn sell_qty(&self) -> i32 {
+        self.working_sell_qty + self.pending_sell_qty + self.placed_sell_qty
+    }
+}
+
+/// Symbol and signed quantity of a working order, from its notifications
+#[derive(Debug, Clone, PartialEq)]
+struct RithmicWorkingOrder {
+    symbol: String,
+    signed_qty: i32,
+}
+
+#[derive(Debug, Default)]
+struct RithmicRiskState {
+    day_pnl: Option<f64>,
+    last_trades: HashMap<String, f64>,
+    positions: HashMap<String, RithmicPosition>,
+    /// Working orders by basket id, None until a notification tells their quantity
+    working_orders: HashMap<String, Option<RithmicWorkingOrder>>,
+    /// Orders checked and being sent, counted as working until Rithmic answers
+    pending_orders: usize,
+}
+
+impl RithmicRiskState {
+    fn apply(&mut self, response: &RithmicResponse) {
+        match &response.message {
+            RithmicMessage::LastTrade(trade) => {
+                if let (Some(symbol), Some(price)) = (&trade.symbol, trade.trade_price) {
+                    self.last_trades.insert(symbol.clone(), price);
+                }
+            }
+            RithmicMessage::AccountPnLPositionUpdate(update) => {
+                if let Some(day_pnl) = update.day_pnl.as_ref().and_then(|p| p.parse().ok()) {
+                    self.day_pnl = Some(day_pnl);
+                }
+            }
+            RithmicMessage::InstrumentPnLPositionUpdate(update) => {
+                if let Some(symbol) = &update.symbol {
+                    let position = self.positions.entry(symbol.clone()).or_default();
+
+                    position.net_qty = update.net_quantity.unwrap_or(position.net_qty);
+                    position.working_buy_qty = update.order_buy_qty.unwrap_or(position.working_buy_qty);
+                    position.working_sell_qty = update.order_sell_qty.unwrap_or(position.working_sell_qty);
+                    // The working quantities now include the orders sent before
+                    position.placed_buy_qty = 0;
+                    position.placed_sell_qty = 0;
+                }
+            }
+            RithmicMessage::RithmicOrderNotification(notification) => {
+                if let Some(basket_id) = &notification.basket_id {
+                    if notification.notify_type == Some(NotifyType::Complete.into()) {
+                        self.working_orders.remove(basket_id);
+                    } else {
+                        let order = self.working_orders.entry(basket_id.clone()).or_default();
+
+                        if let (Some(symbol), Some(qty), Some(transaction_type)) = (
+                            &notification.symbol,
+                            notification.quantity,
+                            notification.transaction_type,
+                        ) {
+                            let signed_qty = if transaction_type == TransactionType::Buy as i32 {
+                                qty
+                            } else {
+                                -qty
+                            };
+
+                            *order = Some(RithmicWorkingOrder {
+                                symbol: symbol.clone(),
+                                signed_qty,
+                            });
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks `orders` orders of `signed_qty` in total and counts them as working until
+    /// `release`, so concurrent orders see them
+    fn reserve(
+        &mut self,
+        config: &RithmicRiskConfig,
+        symbol: &str,
+        signed_qty: i32,
+        price: Option<f64>,
+        orders: usize,
+    ) -> Result<(), RithmicRiskRejection> {
+        self.check(config, symbol, signed_qty, price, orders)?;
+        self.add_pending(symbol, signed_qty, orders);
+
+        Ok(())
+    }
+
+    /// Checks a modify of a working order, returns its symbol and the signed quantity it adds,
+    /// reserved until `release` when positive
+    fn reserve_modify(
+        &mut self,
+        config: &RithmicRiskConfig,
+        order: &RithmicModifyOrder,
+    ) -> Result<(String, i32), RithmicRiskRejection> {
+        if let Some(max) = config.max_order_qty
+            && order.qty > max
+        {
+            return Err(RithmicRiskRejection::MaxOrderQty { qty: order.qty, max });
+        }
+
+        if let Some(max_deviation) = config.price_collar
+            && order.ordertype != request_new_order::PriceType::Market as i32
+        {
+            self.check_collar(&order.symbol, order.price, max_deviation)?;
+        }
+
+        let working = self
+            .working_orders
+            .get(&order.id)
+            .cloned()
+            .flatten()
+            .ok_or_else(|| RithmicRiskRejection::UnknownOrder { id: order.id.clone() })?;
+
+        let added_qty = order.qty - working.signed_qty.abs();
+
+        if added_qty <= 0 {
+            return Ok((working.symbol, 0));
+        }
+
+        let signed_qty = if working.signed_qty > 0 { added_qty } else { -added_qty };
+
+        self.check_position(config, &working.symbol, signed_qty)?;
+        self.check_daily_loss(config)?;
+        self.add_pending(&working.symbol, signed_qty, 0);
+
+        Ok((working.symbol, signed_qty))
+    }
+
+    fn add_pending(&mut self, symbol: &str, signed_qty: i32, orders: usize) {
+        let position = self.positions.entry(symbol.to_string()).or_default();
+
+        if signed_qty > 0 {
+            position.pending_buy_qty += signed_qty;
+        } else {
+            position.pending_sell_qty -= signed_qty;
+        }
+
+        self.pending_orders += orders;
+    }
+
+    /// Ends a reservation, the quantity stays counted until the next position update when the
+    /// order was placed
+    fn release(&mut self, symbol: &str, signed_qty: i32, orders: usize, placed: bool) {
+        let position = self.positions.entry(symbol.to_string()).or_default();
+
+        if signed_qty > 0 {
+            position.pending_buy_qty -= signed_qty;
+
+            if placed {
+                position.placed_buy_qty += signed_qty;
+            }
+        } else {
+            position.pending_sell_qty += signed_qty;
+
+            if placed {
+                position.placed_sell_qty -= signed_qty;
+            }
+        }
+
+        self.pending_orders = self.pending_orders.saturating_sub(orders);
+    }
+
+    fn record_placed(&mut self, responses: &[RithmicResponse]) {
+        for response in responses {
+            let basket_id = match &response.message {
+                RithmicMessage::ResponseNewOrder(resp) => resp.basket_id.clone(),
+                RithmicMessage::ResponseBracketOrder(resp) => resp.basket_id.clone(),
+                _ => None,
+            };
+
+            if let Some(basket_id) = basket_id {
+                self.working_orders.entry(basket_id).or_default();
+            }
+        }
+    }
+
+    fn check(
+        &self,
+        config: &RithmicRiskConfig,
+        symbol: &str,
+        signed_qty: i32,
+        price: Option<f64>,
+        orders: usize,
+    ) -> Result<(), RithmicRiskRejection> {
+        if let Some(max) = config.max_order_qty
+            && signed_qty.abs() > max
+        {
+            return Err(RithmicRiskRejection::MaxOrderQty {
+                qty: signed_qty.abs(),
+                max,
+            });
+        }
+
+        self.check_position(config, symbol, signed_qty)?;
+
+        let working = self.working_orders.len() + self.pending_orders;
+
+        if let Some(max) = config.max_working_orders
+            && working + orders > max
+        {
+            return Err(RithmicRiskRejection::MaxWorkingOrders { working, max });
+        }
+
+        self.check_daily_loss(config)?;
+
+        if let (Some(max_deviation), Some(price)) = (config.price_collar, price) {
+            self.check_collar(symbol, price, max_deviation)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_position(
+        &self,
+        config: &RithmicRiskConfig,
+        symbol: &str,
+        signed_qty: i32,
+    ) -> Result<(), RithmicRiskRejection> {
+        let max_position = config
+            .max_position_by_symbol
+            .get(symbol)
+            .copied()
+            .or(config.max_position);
+
+        if let Some(max) = max_position {
+            let position = self.positions.get(symbol).cloned().unwrap_or_default();
+
+            // Assume every working order on the same side gets filled
+            let worst_case = if signed_qty > 0 {
+                position.net_qty + position.buy_qty() + signed_qty
+            } else {
+                position.net_qty - position.sell_qty() + signed_qty
+            };
+
+            if worst_case.abs() > max {
+                return Err(RithmicRiskRejection::MaxPosition {
+                    symbol: symbol.to_string(),
+                    position: worst_case,
+                    max,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_daily_loss(&self, config: &RithmicRiskConfig) -> Result<(), RithmicRiskRejection> {
+        if let (Some(max_loss), Some(day_pnl)) = (config.max_daily_loss, self.day_pnl)
+            && day_pnl <= -max_loss
+        {
+            return Err(RithmicRiskRejection::DailyLossLimit { day_pnl, max_loss });
+        }
+
+        Ok(())
+    }
+
+    /// Rejects a price too far from the last trade, or when there is no positive last trade
+    /// to compare it with
+    fn check_collar(
+        &self,
+        symbol: &str,
+        price: f64,
+        max_deviation: f64,
+    ) -> Result<(), RithmicRiskRejection> {
+        let last_trade = self
+            .last_trades
+            .get(symbol)
+            .copied()
+            .filter(|last_trade| *last_trade > 0.0)
+            .ok_or_else(|| RithmicRiskRejection::NoReferencePrice {
+                symbol: symbol.to_string(),
+            })?;
+
+        if ((price - last_trade) / last_trade).abs() > max_deviation {
+            return Err(RithmicRiskRejection::PriceCollar {
+                price,
+                last_trade,
+                max_deviation,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Order counted by the risk state while it is sent, released when dropped, including when the
+/// send fails or is cancelled
+struct RithmicReservation {
+    state: Arc<Mutex<RithmicRiskState>>,
+    symbol: String,
+    signed_qty: i32,
+    /// Orders counted as working
+    orders: usize,
+    /// Responses of the placed order
+    placed: Option<Vec<RithmicResponse>>,
+}
+
+impl Drop for RithmicReservation {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.release(&self.symbol, self.signed_qty, self.orders, self.placed.is_some());
+
+        if let Some(responses) = &self.placed {
+            state.record_placed(responses);
+        }
+    }
+}
+
+/// Pre-trade risk checks in front of a `RithmicOrderPlantHandle`.
+///
+/// Market data, PnL and order notifications are fed with `track`, using the subscription
+/// receivers of the ticker, pnl and order plants. Cancels are never blocked.
+#[derive(Clone)]
+pub struct RithmicRiskGate {
+    config: Arc<RithmicRiskConfig>,
+    kill_switch: Arc<AtomicBool>,
+    order_plant: RithmicOrderPlantHandle,
+    state: Arc<Mutex<RithmicRiskState>>,
+}
+
+impl RithmicRiskGate {
+    pub fn new(order_plant: RithmicOrderPlantHandle, config: RithmicRiskConfig) -> Self {
+        RithmicRiskGate {
+            config: Arc::new(config),
+            kill_switch: Arc::new(AtomicBool::new(false)),
+            order_plant,
+            state: Arc::new(Mutex::new(RithmicRiskState::default())),
+        }
+    }
+
+    /// Updates the risk state from a plant subscription until the plant closes
+    pub fn track(&self, mut receiver: broadcast::Receiver<RithmicResponse>) -> JoinHandle<()> {
+        let state = self.state.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(response) => state.lock().unwrap().apply(&response),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        event!(Level::WARN, "risk_gate: lagged, {} updates skipped", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    pub fn apply(&self, response: &RithmicResponse) {
+        self.state.lock().unwrap().apply(response);
+    }
+
+    pub fn set_kill_switch(&self, engaged: bool) {
+        event!(Level::WARN, "risk_gate: kill switch engaged: {}", engaged);
+
+        self.kill_switch.store(engaged, Ordering::SeqCst);
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.kill_switch.load(Ordering::SeqCst)
+    }
+
+    pub fn check(
+        &self,
+        symbol: &str,
+        signed_qty: i32,
+        price: Option<f64>,
+    ) -> Result<(), RithmicRiskRejection> {
+        if self.is_killed() {
+            return Err(RithmicRiskRejection::KillSwitch);
+        }
+
+        self.state
+            .lock()
+            .unwrap()
+            .check(&self.config, symbol, signed_qty, price, 1)
+            .inspect_err(|reason| {
+                event!(Level::WARN, "risk_gate: rejected {} {}: {}", symbol, signed_qty, reason);
+            })
+    }
+
+    /// Same as `check` for `orders` orders of `signed_qty` in total, counting them as working
+    /// until the reservation is dropped
+    fn reserve(
+        &self,
+        symbol: &str,
+        signed_qty: i32,
+        price: Option<f64>,
+        orders: usize,
+    ) -> Result<RithmicReservation, RithmicRiskRejection> {
+        if self.is_killed() {
+            return Err(RithmicRiskRejection::KillSwitch);
+        }
+
+        self.state
+            .lock()
+            .unwrap()
+            .reserve(&self.config, symbol, signed_qty, price, orders)
+            .inspect_err(|reason| {
+                event!(Level::WARN, "risk_gate: rejected {} {}: {}", symbol, signed_qty, reason);
+            })?;
+
+        Ok(RithmicReservation {
+            state: self.state.clone(),
+            symbol: symbol.to_string(),
+            signed_qty,
+            orders,
+            placed: None,
+        })
+    }
+
+    pub async fn place_order(
+        &self,
+        order: RithmicOrder,
+    ) -> Result<Vec<RithmicResponse>, RithmicRiskError> {
+        let signed_qty = match order.action {
+            request_new_order::TransactionType::Buy => order.qty,
+            _ => -order.qty,
+        };
+
+        let price = match order.ordertype {
+            request_new_order::PriceType::Market => None,
+            _ => Some(order.price),
+        };
+
+        let mut reservation = self
+            .reserve(&order.symbol, signed_qty, price, 1)
+            .map_err(RithmicRiskError::Rejected)?;
+
+        let responses = self
+            .order_plant
+            .place_order(order)
+            .await
+            .map_err(RithmicRiskError::Order)?;
+
+        reservation.placed = Some(responses.clone());
+
+        Ok(responses)
+    }
+
+    pub async fn place_bracket_order(
+        &self,
+        bracket_order: RithmicBracketOrder,
+    ) -> Result<Vec<RithmicResponse>, RithmicRiskError> {
+        let signed_qty = if bracket_order.action == request_bracket_order::TransactionType::Buy as i32 {
+            bracket_order.qty
+        } else {
+            -bracket_order.qty
+        };
+
+        let price = if bracket_order.ordertype == request_bracket_order::PriceType::Market as i32 {
+            None
+        } else {
+            bracket_order.price
+        };
+
+        let mut reservation = self
+            .reserve(&bracket_order.symbol, signed_qty, price, 1)
+            .map_err(RithmicRiskError::Rejected)?;
+
+        let responses = self
+            .order_plant
+            .place_bracket_order(bracket_order)
+            .await
+            .map_err(RithmicRiskError::Order)?;
+
+        reservation.placed = Some(responses.clone());
+
+        Ok(responses)
+    }
+
+    /// Pieces of a `RithmicBracketOrderBuilder`, checked as one order of their total quantity
+    /// before the first piece is sent
+    pub async fn place_bracket_orders(
+        &self,
+        bracket_orders: Vec<RithmicBracketOrder>,
+    ) -> Result<Vec<RithmicResponse>, RithmicRiskError> {
+        let Some(first) = bracket_orders.first() else {
+            return Ok(vec![]);
+        };
+
+        if bracket_orders
+            .iter()
+            .any(|order| order.symbol != first.symbol || order.action != first.action)
+        {
+            return Err(RithmicRiskError::Order(
+                "bracket order pieces must share their symbol and side".to_string(),
+            ));
+        }
+
+        let qty: i32 = bracket_orders.iter().map(|order| order.qty).sum();
+
+        let signed_qty = if first.action == request_bracket_order::TransactionType::Buy as i32 {
+            qty
+        } else {
+            -qty
+        };
+
+        let price = if first.ordertype == request_bracket_order::PriceType::Market as i32 {
+            None
+        } else {
+            first.price
+        };
+
+        let mut reservation = self
+            .reserve(&first.symbol, signed_qty, price, bracket_orders.len())
+            .map_err(RithmicRiskError::Rejected)?;
+
+        let responses = self
+            .order_plant
+            .place_bracket_orders(bracket_orders)
+            .await
+            .map_err(RithmicRiskError::Order)?;
+
+        reservation.placed = Some(responses.clone());
+
+        Ok(responses)
+    }
+
+    /// Checks the price and the new quantity of a working order, a quantity increase goes
+    /// through the same size and position checks as a new order. Orders the gate has not seen
+    /// a notification for are rejected.
+    pub async fn modify_order(
+        &self,
+        order: RithmicModifyOrder,
+    ) -> Result<RithmicResponse, RithmicRiskError> {
+        if self.is_killed() {
+            return Err(RithmicRiskError::Rejected(RithmicRiskRejection::KillSwitch));
+        }
+
+        let (symbol, signed_qty) = self
+            .state
+            .lock()
+            .unwrap()
+            .reserve_modify(&self.config, &order)
+            .inspect_err(|reason| {
+                event!(Level::WARN, "risk_gate: rejected modify of {}: {}", order.id, reason);
+            })
+            .map_err(RithmicRiskError::Rejected)?;
+
+        let mut reservation = RithmicReservation {
+            state: self.state.clone(),
+            symbol,
+            signed_qty,
+            orders: 0,
+            placed: None,
+        };
+
+        let response = self
+            .order_plant
+            .modify_order(order)
+            .await
+            .map_err(RithmicRiskError::Order)?;
+
+        reservation.placed = Some(vec![]);
+
+        Ok(response)
+    }
+
+    pub async fn cancel_order(
+        &self,
+        order: RithmicCancelOrder,
+    ) -> Result<RithmicResponse, RithmicRiskError> {
+        self.order_plant
+            .cancel_order(order)
+            .await
+            .map_err(RithmicRiskError::Order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RithmicRiskConfig {
+        RithmicRiskConfig {
+            max_order_qty: Some(10),
+            max_position: Some(5),
+            max_working_orders: Some(3),
+            ..RithmicRiskConfig::default()
+        }
+    }
+
+    /// State with a working buy order "1" of `qty` ESZ5
+    fn state_with_order(qty: i32) -> RithmicRiskState {
+        let mut state = RithmicRiskState::default();
+
+        state.working_orders.insert(
+            "1".to_string(),
+            Some(RithmicWorkingOrder {
+                symbol: "ESZ5".to_string(),
+                signed_qty: qty,
+            }),
+        );
+
+        state.positions.insert(
+            "ESZ5".to_string(),
+            RithmicPosition {
+                working_buy_qty: qty,
+                ..RithmicPosition::default()
+            },
+        );
+
+        state
+    }
+
+    fn modify(id: &str, qty: i32) -> RithmicModifyOrder {
+        RithmicModifyOrder {
+            id: id.to_string(),
+            exchange: "CME".to_string(),
+            symbol: "ESZ5".to_string(),
+            qty,
+            price: 0.0,
+            ordertype: request_new_order::PriceType::Market as i32,
+        }
+    }
+
+    #[test]
+    fn modify_raising_the_quantity_is_checked() {
+        let config = config();
+        let mut state = state_with_order(1);
+
+        assert_eq!(
+            state.reserve_modify(&config, &modify("1", 500)),
+            Err(RithmicRiskRejection::MaxOrderQty { qty: 500, max: 10 })
+        );
+        assert_eq!(
+            state.reserve_modify(&config, &modify("1", 8)),
+            Err(RithmicRiskRejection::MaxPosition {
+                symbol: "ESZ5".to_string(),
+                position: 8,
+                max: 5
+            })
+        );
+        assert_eq!(state.reserve_modify(&config, &modify("1", 4)), Ok(("ESZ5".to_string(), 3)));
+        assert_eq!(state.positions["ESZ5"].pending_buy_qty, 3);
+    }
+
+    #[test]
+    fn modify_lowering_the_quantity_reserves_nothing() {
+        let config = config();
+        let mut state = state_with_order(4);
+
+        assert_eq!(state.reserve_modify(&config, &modify("1", 2)), Ok(("ESZ5".to_string(), 0)));
+        assert_eq!(state.positions["ESZ5"].buy_qty(), 4);
+    }
+
+    #[test]
+    fn modify_of_an_unknown_order_is_rejected() {
+        let mut state = state_with_order(1);
+
+        state.working_orders.insert("2".to_string(), None);
+
+        for id in ["2", "3"] {
+            assert_eq!(
+                state.reserve_modify(&config(), &modify(id, 1)),
+                Err(RithmicRiskRejection::UnknownOrder { id: id.to_string() })
+            );
+        }
+    }
+
+    #[test]
+    fn bracket_pieces_reserve_their_total() {
+        let config = config();
+        let mut state = RithmicRiskState::default();
+
+        assert!(matches!(
+            state.reserve(&config, "ESZ5", 6, None, 2),
+            Err(RithmicRiskRejection::MaxPosition { .. })
+        ));
+        assert_eq!(state.reserve(&config, "ESZ5", 4, None, 2), Ok(()));
+        assert_eq!(state.pending_orders, 2);
+        assert_eq!(
+            state.reserve(&config, "ESZ5", -1, None, 2),
+            Err(RithmicRiskRejection::MaxWorkingOrders { working: 2, max: 3 })
+        );
+
+        state.release("ESZ5", 4, 2, true);
+
+        assert_eq!(state.pending_orders, 0);
+        assert_eq!(state.positions["ESZ5"].buy_qty(), 4);
+    }
+}
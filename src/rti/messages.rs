@@ -1,9 +1,11 @@
 use super::*;
+use crate::ws::RithmicSessionEvent;
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum RithmicMessage {
     AccountPnLPositionUpdate(AccountPnLPositionUpdate),
+    AccountRmsUpdates(AccountRmsUpdates),
     BestBidOffer(BestBidOffer),
     BracketUpdates(BracketUpdates),
     DepthByOrder(DepthByOrder),
@@ -14,17 +16,26 @@ pub enum RithmicMessage {
     OpenInterest(OpenInterest),
     OrderBook(OrderBook),
     Reject(Reject),
+    ResponseAcceptAgreement(ResponseAcceptAgreement),
     ResponseAccountList(ResponseAccountList),
     ResponseAccountRmsInfo(ResponseAccountRmsInfo),
+    ResponseAccountRmsUpdates(ResponseAccountRmsUpdates),
+    ResponseAuxilliaryReferenceData(ResponseAuxilliaryReferenceData),
     ResponseBracketOrder(ResponseBracketOrder),
     ResponseCancelAllOrders(ResponseCancelAllOrders),
     ResponseCancelOrder(ResponseCancelOrder),
     ResponseExitPosition(ResponseExitPosition),
     ResponseGetInstrumentByUnderlying(ResponseGetInstrumentByUnderlying),
+    ResponseGetInstrumentByUnderlyingKeys(ResponseGetInstrumentByUnderlyingKeys),
+    ResponseGetVolumeAtPrice(ResponseGetVolumeAtPrice),
+    ResponseGiveTickSizeTypeTable(ResponseGiveTickSizeTypeTable),
     ResponseHeartbeat(ResponseHeartbeat),
+    ResponseListAcceptedAgreements(ResponseListAcceptedAgreements),
+    ResponseListUnacceptedAgreements(ResponseListUnacceptedAgreements),
     ResponseLogin(ResponseLogin),
     ResponseLogout(ResponseLogout),
     ResponseMarketDataUpdate(ResponseMarketDataUpdate),
+    ResponseMarketDataUpdateByUnderlying(ResponseMarketDataUpdateByUnderlying),
     ResponseModifyOrder(ResponseModifyOrder),
     ResponseNewOrder(ResponseNewOrder),
     ResponsePnLPositionSnapshot(ResponsePnLPositionSnapshot),
@@ -32,9 +43,13 @@ pub enum RithmicMessage {
     ResponseProductCodes(ResponseProductCodes),
     ResponseProductRmsInfo(ResponseProductRmsInfo),
     ResponseReferenceData(ResponseReferenceData),
+    ResponseReplayExecutions(ResponseReplayExecutions),
+    ResponseResumeBars(ResponseResumeBars),
     ResponseRithmicSystemInfo(ResponseRithmicSystemInfo),
     ResponseRithmicSystemGatewayInfo(ResponseRithmicSystemGatewayInfo),
     ResponseSearchSymbols(ResponseSearchSymbols),
+    ResponseSetRithmicMrktDataSelfCertStatus(ResponseSetRithmicMrktDataSelfCertStatus),
+    ResponseShowAgreement(ResponseShowAgreement),
     ResponseShowBrackets(ResponseShowBrackets),
     ResponseShowBracketStops(ResponseShowBracketStops),
     ResponseShowOrderHistory(ResponseShowOrderHistory),
@@ -53,6 +68,7 @@ pub enum RithmicMessage {
     ResponseUpdateTargetBracketLevel(ResponseUpdateTargetBracketLevel),
     ResponseVolumeProfileMinuteBars(ResponseVolumeProfileMinuteBars),
     RithmicOrderNotification(RithmicOrderNotification),
+    SessionEvent(RithmicSessionEvent),
     TickBar(TickBar),
     TimeBar(TimeBar),
 }
@@ -0,0 +1,294 @@
+use std::{collections::VecDeque, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{event, Level};
+
+/// Token bucket settings for the messages a plant sends to Rithmic
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RithmicThrottleConfig {
+    /// Sustained rate
+    pub messages_per_second: f64,
+    /// Number of messages that can be sent at once after an idle period
+    pub burst: u32,
+    /// Queueing delay above which a warning is logged
+    pub warn_delay_ms: u64,
+    /// Number of queued messages above which new ones are refused
+    #[serde(default = "default_max_queued")]
+    pub max_queued: usize,
+}
+
+fn default_max_queued() -> usize {
+    1000
+}
+
+impl RithmicThrottleConfig {
+    /// Checks the settings, they may come from a file
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.messages_per_second.is_finite() || self.messages_per_second <= 0.0 {
+            return Err(format!(
+                "throttle: messages_per_second must be positive, got {}",
+                self.messages_per_second
+            ));
+        }
+
+        if self.burst == 0 {
+            return Err("throttle: burst must be at least 1".to_string());
+        }
+
+        if self.max_queued == 0 {
+            return Err("throttle: max_queued must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RithmicThrottleStats {
+    pub sent: u64,
+    pub queued: usize,
+    pub last_delay: Duration,
+    pub max_delay: Duration,
+}
+
+/// Queues outgoing messages and releases them according to a token bucket.
+///
+/// Priority messages (cancels) always leave before normal ones.
+#[derive(Debug)]
+pub struct RithmicThrottle {
+    config: RithmicThrottleConfig,
+    last_refill: Instant,
+    normal: VecDeque<(Message, Instant)>,
+    priority: VecDeque<(Message, Instant)>,
+    stats: RithmicThrottleStats,
+    tokens: f64,
+}
+
+impl RithmicThrottle {
+    pub fn new(config: &RithmicThrottleConfig) -> Result<RithmicThrottle, String> {
+        config.validate()?;
+
+        Ok(RithmicThrottle {
+            config: config.clone(),
+            last_refill: Instant::now(),
+            normal: VecDeque::new(),
+            priority: VecDeque::new(),
+            stats: RithmicThrottleStats::default(),
+            tokens: config.burst as f64,
+        })
+    }
+
+    /// Queues a message, refused when `max_queued` messages are already waiting
+    pub fn push(&mut self, message: Message, priority: bool) -> Result<(), String> {
+        let queued = self.priority.len() + self.normal.len();
+
+        if queued >= self.config.max_queued {
+            return Err(format!("throttle: queue full, {} messages waiting", queued));
+        }
+
+        let entry = (message, Instant::now());
+
+        if priority {
+            self.priority.push_back(entry);
+        } else {
+            self.normal.push_back(entry);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.priority.is_empty() && self.normal.is_empty()
+    }
+
+    /// Instant at which the next queued message can be sent
+    pub fn ready_at(&mut self) -> Instant {
+        self.refill();
+
+        if self.tokens >= 1.0 {
+            Instant::now()
+        } else {
+            let missing = 1.0 - self.tokens;
+
+            Instant::now() + Duration::from_secs_f64(missing / self.config.messages_per_second)
+        }
+    }
+
+    /// Next message to send, if a token is available
+    pub fn pop(&mut self) -> Option<Message> {
+        self.refill();
+
+        if self.tokens < 1.0 {
+            return None;
+        }
+
+        let (message, queued_at) = self
+            .priority
+            .pop_front()
+            .or_else(|| self.normal.pop_front())?;
+
+        self.tokens -= 1.0;
+
+        let delay = queued_at.elapsed();
+
+        self.stats.sent += 1;
+        self.stats.last_delay = delay;
+        self.stats.max_delay = self.stats.max_delay.max(delay);
+
+        if delay > Duration::from_millis(self.config.warn_delay_ms) {
+            event!(
+                Level::WARN,
+                "throttle: message delayed by {:?}, {} still queued",
+                delay,
+                self.priority.len() + self.normal.len()
+            );
+        }
+
+        Some(message)
+    }
+
+    pub fn stats(&self) -> RithmicThrottleStats {
+        RithmicThrottleStats {
+            queued: self.priority.len() + self.normal.len(),
+            ..self.stats.clone()
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.config.messages_per_second)
+            .min(self.config.burst as f64);
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::advance;
+
+    use super::*;
+
+    fn throttle(messages_per_second: f64, burst: u32, max_queued: usize) -> RithmicThrottle {
+        RithmicThrottle::new(&RithmicThrottleConfig {
+            messages_per_second,
+            burst,
+            warn_delay_ms: 1000,
+            max_queued,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tokens_refill_at_the_configured_rate() {
+        let mut throttle = throttle(10.0, 2, 10);
+
+        for text in ["1", "2", "3"] {
+            throttle.push(Message::text(text), false).unwrap();
+        }
+
+        assert_eq!(throttle.pop(), Some(Message::text("1")));
+        assert_eq!(throttle.pop(), Some(Message::text("2")));
+        assert_eq!(throttle.pop(), None);
+        assert_eq!(throttle.ready_at(), Instant::now() + Duration::from_millis(100));
+
+        advance(Duration::from_millis(100)).await;
+
+        assert_eq!(throttle.pop(), Some(Message::text("3")));
+        assert!(throttle.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_caps_the_refill() {
+        let mut throttle = throttle(10.0, 2, 10);
+
+        advance(Duration::from_secs(10)).await;
+
+        for text in ["1", "2", "3"] {
+            throttle.push(Message::text(text), false).unwrap();
+        }
+
+        assert!(throttle.pop().is_some());
+        assert!(throttle.pop().is_some());
+        assert_eq!(throttle.pop(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancels_leave_before_queued_orders() {
+        let mut throttle = throttle(1.0, 1, 10);
+
+        throttle.push(Message::text("order 1"), false).unwrap();
+        throttle.push(Message::text("order 2"), false).unwrap();
+        throttle.push(Message::text("cancel"), true).unwrap();
+
+        assert_eq!(throttle.pop(), Some(Message::text("cancel")));
+
+        advance(Duration::from_secs(1)).await;
+
+        assert_eq!(throttle.pop(), Some(Message::text("order 1")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_queue_refuses_messages() {
+        let mut throttle = throttle(1.0, 1, 2);
+
+        throttle.push(Message::text("1"), false).unwrap();
+        throttle.push(Message::text("2"), true).unwrap();
+
+        assert!(throttle.push(Message::text("3"), true).is_err());
+        assert_eq!(throttle.stats().queued, 2);
+
+        assert!(throttle.pop().is_some());
+        assert!(throttle.push(Message::text("3"), false).is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stats_report_the_queueing_delay() {
+        let mut throttle = throttle(2.0, 1, 10);
+
+        throttle.push(Message::text("1"), false).unwrap();
+        throttle.push(Message::text("2"), false).unwrap();
+        throttle.push(Message::text("3"), false).unwrap();
+
+        assert!(throttle.pop().is_some());
+
+        advance(Duration::from_millis(500)).await;
+
+        assert!(throttle.pop().is_some());
+
+        advance(Duration::from_millis(500)).await;
+
+        assert!(throttle.pop().is_some());
+
+        let stats = throttle.stats();
+
+        assert_eq!(stats.sent, 3);
+        assert_eq!(stats.queued, 0);
+        assert_eq!(stats.last_delay, Duration::from_secs(1));
+        assert_eq!(stats.max_delay, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let config = RithmicThrottleConfig {
+            messages_per_second: 0.0,
+            burst: 1,
+            warn_delay_ms: 0,
+            max_queued: 1,
+        };
+
+        assert!(RithmicThrottle::new(&config).is_err());
+
+        let config = RithmicThrottleConfig {
+            messages_per_second: 1.0,
+            ..config
+        };
+
+        assert!(RithmicThrottle::new(&config).is_ok());
+        assert!(RithmicThrottle::new(&RithmicThrottleConfig { burst: 0, ..config.clone() }).is_err());
+        assert!(RithmicThrottle::new(&RithmicThrottleConfig { max_queued: 0, ..config }).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use aws_lc_rs::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use tokio_rustls::rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    crypto::{aws_lc_rs::default_provider, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use tracing::{event, Level};
+
+/// TLS settings of the plant connections.
+///
+/// Without any setting the system root certificates are trusted, as with the default connector.
+///
+/// ```toml
+/// [tls]
+/// ca_file = "/etc/rithmic/ca.pem"
+/// pinned_sha256 = ["3f:1a:..."]
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RithmicTlsConfig {
+    /// PEM bundle trusted instead of the system root certificates
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    /// Also trust the system root certificates when `ca_file` is set
+    #[serde(default)]
+    pub native_roots: bool,
+    /// SHA-256 fingerprints of the accepted server certificates, hex with or without colons.
+    /// The server certificate must match one of them on top of the chain validation.
+    #[serde(default)]
+    pub pinned_sha256: Vec<String>,
+    /// Only check the pins and skip the chain validation, e.g. a mock server with a self-signed
+    /// certificate
+    #[serde(default)]
+    pub pinned_only: bool,
+    /// PEM certificate chain sent to the server
+    #[serde(default)]
+    pub client_cert_file: Option<String>,
+    /// PEM private key of `client_cert_file`
+    #[serde(default)]
+    pub client_key_file: Option<String>,
+    /// Used as is instead of the settings above
+    #[serde(skip)]
+    pub client_config: Option<Arc<ClientConfig>>,
+}
+
+impl RithmicTlsConfig {
+    /// Uses `client_config` for every connection
+    pub fn with_client_config(client_config: Arc<ClientConfig>) -> RithmicTlsConfig {
+        RithmicTlsConfig {
+            client_config: Some(client_config),
+            ..RithmicTlsConfig::default()
+        }
+    }
+
+    /// Trusts the certificates of `ca_file` only
+    pub fn with_ca_file(ca_file: &str) -> RithmicTlsConfig {
+        RithmicTlsConfig {
+            ca_file: Some(ca_file.to_string()),
+            ..RithmicTlsConfig::default()
+        }
+    }
+
+    /// Adds `fingerprint` to the accepted server certificates
+    pub fn with_pin(mut self, fingerprint: &str) -> RithmicTlsConfig {
+        self.pinned_sha256.push(fingerprint.to_string());
+        self
+    }
+
+    pub fn client_config(&self) -> Result<Arc<ClientConfig>, anyhow::Error> {
+        if let Some(client_config) = &self.client_config {
+            return Ok(client_config.clone());
+        }
+
+        let provider = Arc::new(default_provider());
+
+        let mut roots = RootCertStore::empty();
+
+        if let Some(ca_file) = &self.ca_file {
+            for cert in CertificateDer::pem_file_iter(ca_file)
+                .map_err(|e| anyhow!("failed to read {}: {}", ca_file, e))?
+            {
+                roots.add(cert.map_err(|e| anyhow!("invalid certificate in {}: {}", ca_file, e))?)?;
+            }
+        }
+
+        if self.ca_file.is_none() || self.native_roots {
+            let native_certs = rustls_native_certs::load_native_certs();
+
+            for e in native_certs.errors {
+                event!(Level::WARN, "tls: failed to load a system root certificate: {}", e);
+            }
+
+            roots.add_parsable_certificates(native_certs.certs);
+        }
+
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+
+        let builder = if self.pinned_sha256.is_empty() {
+            builder.with_root_certificates(roots)
+        } else {
+            let pins = self
+                .pinned_sha256
+                .iter()
+                .map(|pin| parse_fingerprint(pin))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let chain_verifier = if self.pinned_only {
+                None
+            } else {
+                Some(WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone()).build()?)
+            };
+
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier {
+                    chain_verifier,
+                    pins,
+                    provider,
+                }))
+        };
+
+        let client_config = match (&self.client_cert_file, &self.client_key_file) {
+            (Some(cert_file), Some(key_file)) => {
+                let certs = CertificateDer::pem_file_iter(cert_file)
+                    .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                    .map_err(|e| anyhow!("failed to read {}: {}", cert_file, e))?;
+
+                let key = PrivateKeyDer::from_pem_file(key_file)
+                    .map_err(|e| anyhow!("failed to read {}: {}", key_file, e))?;
+
+                builder.with_client_auth_cert(certs, key)?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => return Err(anyhow!("client_cert_file and client_key_file must be set together")),
+        };
+
+        Ok(Arc::new(client_config))
+    }
+}
+
+/// Checks the server certificate against the pins, after the chain validation when there is one
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    chain_verifier: Option<Arc<WebPkiServerVerifier>>,
+    pins: Vec<Vec<u8>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        if let Some(chain_verifier) = &self.chain_verifier {
+            chain_verifier.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+
+        let fingerprint = digest(&SHA256, end_entity.as_ref());
+
+        if self.pins.iter().any(|pin| pin.as_slice() == fingerprint.as_ref()) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            event!(
+                Level::ERROR,
+                "tls: certificate of {:?} does not match any pin, fingerprint {}",
+                server_name,
+                to_hex(fingerprint.as_ref())
+            );
+
+            Err(tokio_rustls::rustls::Error::General(
+                "server certificate does not match any pin".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// SHA-256 fingerprint of a DER certificate, hex with colons as printed by
+/// `openssl x509 -noout -fingerprint -sha256`
+pub fn certificate_fingerprint(cert: &[u8]) -> String {
+    to_hex(digest(&SHA256, cert).as_ref())
+}
+
+fn parse_fingerprint(fingerprint: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let hex: String = fingerprint.chars().filter(|c| *c != ':').collect();
+
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(anyhow!("invalid sha256 fingerprint {}", fingerprint));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| anyhow!("invalid sha256 fingerprint {}", fingerprint))
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
@@ -1,18 +1,17 @@
-use std::env;
 use std::time::Duration;
 use anyhow::anyhow;
 use async_trait::async_trait;
-use base64::Engine;
-use base64::prelude::BASE64_STANDARD;
-use bytes::Bytes;
-use http::{Request, Uri};
-use http::header::PROXY_AUTHORIZATION;
+use http::Uri;
 use tokio::net::TcpStream;
 use tokio::time::{interval_at, Instant, Interval};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 use tokio_tungstenite::tungstenite::{Error, Message};
 use tungstenite::client::IntoClientRequest;
 
+use tokio_tungstenite::Connector;
+
+use crate::{proxy::RithmicProxyConfig, tls::RithmicTlsConfig};
+
 pub trait RithmicStream {
     type Handle;
 
@@ -28,6 +27,21 @@ pub trait PlantActor {
     async fn handle_rithmic_message(&mut self, message: Result<Message, Error>) -> Result<bool, ()>;
 }
 
+/// Session state changes, sent to a plant's subscribers as `RithmicMessage::SessionEvent`
+#[derive(Debug, Clone, PartialEq)]
+pub enum RithmicSessionEvent {
+    LoggedIn,
+    /// Rithmic logged the session out, pending requests have been failed
+    ForcedLogout,
+    /// Rithmic rejected a request, the request has been failed with `reason`
+    Rejected {
+        request_id: String,
+        reason: Option<String>,
+    },
+    /// The websocket was closed, pending requests have been failed
+    ConnectionClosed,
+}
+
 pub fn get_heartbeat_interval() -> Interval {
     let heartbeat_interval = Duration::from_secs(60);
     let start_offset = Instant::now() + heartbeat_interval;
@@ -35,49 +49,60 @@ pub fn get_heartbeat_interval() -> Interval {
     interval_at(start_offset, heartbeat_interval)
 }
 
-pub async fn connect(url: &str) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, anyhow::Error> {
+/// Opens the websocket to `url` through `proxy`, or the proxy set in the environment when None.
+///
+/// `tls` replaces the default connector trusting the system root certificates.
+pub async fn connect(
+    url: &str,
+    proxy: Option<&RithmicProxyConfig>,
+    tls: Option<&RithmicTlsConfig>,
+) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, anyhow::Error> {
     let ws_uri: Uri = url.parse()?;
 
-    if let Ok(proxy_url_str) = env::var("HTTPS_PROXY") {
-        let proxy_uri: hyper::Uri = proxy_url_str.parse()?;
+    let host = ws_uri
+        .host()
+        .ok_or_else(|| anyhow!("invalid url {}, missing host", url))?
+        .to_string();
 
-        // Établir une connexion TCP avec le proxy
-        let stream = hyper_util::rt::TokioIo::new(TcpStream::connect(format!("{}:{}", proxy_uri.host().unwrap_or_default(), proxy_uri.port_u16().unwrap_or(80))).await?);
+    let port = ws_uri.port_u16().unwrap_or(match ws_uri.scheme_str() {
+        Some("ws") => 80,
+        _ => 443,
+    });
 
-        let (mut request_sender, conn) = hyper::client::conn::http1::handshake(stream).await?;
-        let conn = tokio::spawn(conn.without_shutdown());
+    let proxy = proxy
+        .cloned()
+        .or_else(RithmicProxyConfig::from_env)
+        .filter(|proxy| !proxy.bypasses(&host));
 
-        let mut request_builder = Request::connect(format!("{}:{}", ws_uri.host().unwrap_or_default(), ws_uri.port_u16().unwrap_or(443)));
-        // Ajoute l'authentification si présente dans l'URL du proxy
-        if let Some(auth) = proxy_uri.authority() {
-            if let Some((username, password)) = auth.as_str().split_once('@') {
-                let credentials = format!("{}:{}", username, password.splitn(2, ':').next().unwrap_or(""));
-                let auth = format!("Basic {}", BASE64_STANDARD.encode(credentials));
-                request_builder = request_builder.header(PROXY_AUTHORIZATION, auth);
-            }
-        }
-        let request = request_builder.body(http_body_util::Empty::<Bytes>::new())?;
+    let connector = match tls {
+        Some(tls) => Some(Connector::Rustls(tls.client_config()?)),
+        None => None,
+    };
 
-        let res = request_sender.send_request(request).await?;
+    match proxy {
+        Some(proxy) => {
+            let tcp = proxy.tunnel(&host, port).await?;
 
-        if !res.status().is_success() {
-            return Err(anyhow!(
-                "The proxy server returned an error response: status code: {}, body: {:#?}",
-                res.status(),
-                res.body()
-            ));
+            let ws_stream = tokio_tungstenite::client_async_tls_with_config(
+                ws_uri.into_client_request()?,
+                tcp,
+                None,
+                connector,
+            )
+            .await?
+            .0;
+            Ok(ws_stream)
+        }
+        None => {
+            let ws_stream = tokio_tungstenite::connect_async_tls_with_config(
+                ws_uri.into_client_request()?,
+                None,
+                false,
+                connector,
+            )
+            .await?
+            .0;
+            Ok(ws_stream)
         }
-
-        let tcp = conn.await
-            .map_err(|e| anyhow!(e))??
-            .io
-            .into_inner();
-
-        // CryptoProvider::install_default();
-        let ws_stream = tokio_tungstenite::client_async_tls(ws_uri.into_client_request()?, tcp).await?.0;
-        Ok(ws_stream)
-    } else {
-        let ws_stream = tokio_tungstenite::connect_async(ws_uri.into_client_request()?).await?.0;
-        Ok(ws_stream)
     }
-}
\ No newline at end of file
+}